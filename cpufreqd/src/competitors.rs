@@ -0,0 +1,76 @@
+use crate::fs::{Fs, OpenOptions};
+
+/// daemons known to manage cpu frequencies themselves, as `(process name, daemon name)`. process
+/// names (`/proc/<pid>/comm`) are truncated to 15 bytes
+pub(crate) const COMPETITORS: &[(&str, &str)] = &[
+    ("auto-cpufreq", "auto-cpufreq"),
+    ("power-profiles-", "power-profiles-daemon"),
+    ("system76-power", "system76-power"),
+    ("thermald", "thermald"),
+    ("tlp", "tlp"),
+    ("tuned", "tuned"),
+    ("tuned-ppd", "tuned-ppd"),
+];
+
+/// the names of the [COMPETITORS] that are running, sorted and deduplicated. this is best-effort:
+/// processes whose `comm` cannot be read are ignored, and one-shot tools like TLP are only seen
+/// while they run
+pub(crate) fn detect_competitors<F: Fs>(fs: &F) -> Vec<String> {
+    let Ok(processes) = fs.dir("/proc") else {
+        return Vec::new();
+    };
+    let mut running: Vec<String> = processes
+        .iter()
+        .filter(|process| F::is_dir(process))
+        .filter_map(|process| {
+            let path = F::dirent_path(process);
+            let pid = path.file_name()?.to_str()?;
+            if !pid.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            let mut comm = fs
+                .open(
+                    OpenOptions::default().read(true),
+                    &format!("/proc/{pid}/comm"),
+                )
+                .ok()?;
+            let comm = fs.read_to_string(&mut comm).ok()?;
+            COMPETITORS
+                .iter()
+                .find(|(process, _)| *process == comm.trim())
+                .map(|(_, name)| name.to_string())
+        })
+        .collect();
+    running.sort_unstable();
+    running.dedup();
+    running
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::fs::TestFs;
+
+    #[test]
+    fn detects_running_competitors() {
+        let processes = [
+            ("/proc/1/comm", "systemd\n"),
+            ("/proc/412/comm", "tuned\n"),
+            ("/proc/413/comm", "power-profiles-\n"),
+            ("/proc/900/comm", "tuned\n"),
+            ("/proc/self/comm", "thermald\n"),
+            ("/proc/cpuinfo", "tlp\n"),
+        ];
+        let fs = TestFs(
+            processes
+                .iter()
+                .map(|(path, comm)| (Arc::from(Path::new(path)), comm.to_string()))
+                .collect(),
+        );
+        assert_eq!(detect_competitors(&fs), ["power-profiles-daemon", "tuned"]);
+        assert!(detect_competitors(&TestFs::new(&[])).is_empty());
+    }
+}
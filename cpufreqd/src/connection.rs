@@ -0,0 +1,171 @@
+//! the daemon's end of a client connection: framed requests in, framed responses out
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+use libcpufreq::{Clock, Features, FrameEncoder, Request, Response, read_payload};
+
+use crate::fs::Fs;
+use crate::server::Server;
+
+/// answer the requests read from `reader` on `writer` until the client disconnects. `features`
+/// are the ones the handshake agreed on, see [accept_features](libcpufreq::accept_features).
+///
+/// streaming requests run on their own threads so that the connection goes on answering requests,
+/// eg [Request::Unsubscribe], while they stream. the streams a connection started are stopped and
+/// waited for once it ends.
+///
+/// a client that disconnects between frames ends the connection with `Ok(())`. one that
+/// disconnects part way through a frame, or sends a request that does not decode, is an error
+pub(crate) fn serve_connection<F, C, R, W>(
+    mut server: Server<F, C>,
+    features: Features,
+    mut reader: R,
+    writer: W,
+) -> io::Result<()>
+where
+    F: Fs + Clone + Send,
+    C: Clock + Clone + Send,
+    R: Read,
+    W: Write + Send,
+{
+    let writer = Mutex::new((writer, FrameEncoder::with_features(features)));
+    let mut streams = Vec::new();
+    let emit = |response: Response<io::Error>| {
+        let mut writer = writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let (writer, encoder) = &mut *writer;
+        encoder.write(writer, &response.to_string_error()).is_ok()
+    };
+    thread::scope(|scope| {
+        let result = loop {
+            let payload = match read_payload(&mut reader, features) {
+                Ok(Some(payload)) => payload,
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            };
+            let request: Request =
+                match bincode::decode_from_slice(&payload, bincode::config::standard()) {
+                    Ok((request, _)) => request,
+                    Err(e) => break Err(io::Error::new(ErrorKind::InvalidData, e)),
+                };
+            if request.is_streaming() {
+                let id = server.new_subscription();
+                streams.push(id);
+                let mut stream = server.fork();
+                scope.spawn(move || stream.serve_stream(id, request, emit));
+            } else {
+                server.serve(request, emit);
+            }
+        };
+        // the streams end at their next poll and the scope waits for them
+        let mut state = server.state().lock();
+        for id in &streams {
+            state.subscriptions.remove(id);
+        }
+        result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use libcpufreq::{CpuCores, StringError, write_frame};
+
+    use super::*;
+    use crate::fs::TestFs;
+
+    fn server() -> Server<TestFs> {
+        let files = [
+            ("/sys/devices/system/cpu/present", "0-1\n"),
+            ("/sys/devices/system/cpu/online", "0-1\n"),
+            (
+                "/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+                "powersave\n",
+            ),
+            (
+                "/sys/devices/system/cpu/cpu1/cpufreq/scaling_governor",
+                "powersave\n",
+            ),
+        ];
+        Server::new(TestFs(
+            files
+                .iter()
+                .map(|(path, content)| (Arc::from(Path::new(path)), content.to_string()))
+                .collect(),
+        ))
+    }
+
+    fn requests(requests: &[Request]) -> Vec<u8> {
+        let mut input = Vec::new();
+        let mut encoder = FrameEncoder::default();
+        for request in requests {
+            encoder.write(&mut input, request).unwrap();
+        }
+        input
+    }
+
+    fn responses(mut output: &[u8]) -> Vec<Response<StringError>> {
+        let mut responses = Vec::new();
+        while let Some(payload) = read_payload(&mut output, Features::default()).unwrap() {
+            let (response, _) =
+                bincode::decode_from_slice(&payload, bincode::config::standard()).unwrap();
+            responses.push(response);
+        }
+        responses
+    }
+
+    #[test]
+    fn a_disconnect_between_frames_ends_the_connection_cleanly() {
+        let input = requests(&[Request::CoreCount, Request::CoreCount]);
+        let mut output = Vec::new();
+        serve_connection(server(), Features::default(), input.as_slice(), &mut output).unwrap();
+        let responses = responses(&output);
+        assert_eq!(responses.len(), 2);
+        assert!(
+            responses
+                .iter()
+                .all(|response| matches!(response, Response::CoreCount(2)))
+        );
+    }
+
+    #[test]
+    fn a_disconnect_part_way_through_a_frame_is_an_error() {
+        let mut input = requests(&[Request::CoreCount]);
+        input.extend_from_slice(&[0, 0]);
+        let mut output = Vec::new();
+        let error = serve_connection(server(), Features::default(), input.as_slice(), &mut output)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+        // the request before the partial frame was still answered
+        assert!(matches!(
+            responses(&output).as_slice(),
+            [Response::CoreCount(2)]
+        ));
+    }
+
+    #[test]
+    fn a_request_that_does_not_decode_is_an_error() {
+        let mut input = Vec::new();
+        write_frame(&mut input, &[0xff; 8]).unwrap();
+        let error = serve_connection(server(), Features::default(), input.as_slice(), Vec::new())
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn streams_end_with_the_connection() {
+        let input = requests(&[Request::WatchChanges(CpuCores::All)]);
+        let mut output = Vec::new();
+        let server = server();
+        let state = server.state().clone();
+        serve_connection(server, Features::default(), input.as_slice(), &mut output).unwrap();
+        assert!(state.lock().subscriptions.is_empty());
+        assert!(matches!(
+            responses(&output).first(),
+            Some(Response::Subscribed(_))
+        ));
+    }
+}
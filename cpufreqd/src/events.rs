@@ -0,0 +1,78 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// something that happened in the server that subscription loops may want to tell clients about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Event {
+    /// the server set a core's governor
+    GovernorApplied { core: u8, governor: String },
+    /// something other than the server changed a core's governor
+    ExternalChange {
+        core: u8,
+        previous: String,
+        current: String,
+    },
+    /// the thermal guard started limiting `scaling_max_freq`, at this temperature in
+    /// millidegrees Celsius
+    ThermalEngaged { temperature_mc: u64 },
+    /// the thermal guard restored the previous limits, at this temperature in millidegrees
+    /// Celsius
+    ThermalReleased { temperature_mc: u64 },
+}
+
+/// hands every published [Event] to every subscriber, so that handlers producing events need not
+/// know who consumes them. each subscriber has its own queue, so one that reads slowly does not
+/// hold up the others, and subscribers that were dropped are forgotten on the next publish
+#[derive(Debug, Default)]
+pub(crate) struct EventBus {
+    subscribers: Vec<Sender<Event>>,
+}
+
+impl EventBus {
+    pub(crate) fn publish(&mut self, event: Event) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    /// a queue of the events published from now on. poll loops drain it with
+    /// [Receiver::try_iter]
+    pub(crate) fn subscribe(&mut self) -> Receiver<Event> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_subscriber_receives_published_events() {
+        let mut bus = EventBus::default();
+        let first = bus.subscribe();
+        let applied = Event::GovernorApplied {
+            core: 2,
+            governor: "performance".into(),
+        };
+        bus.publish(applied.clone());
+        // subscribers only see events published after they subscribed
+        let second = bus.subscribe();
+        let engaged = Event::ThermalEngaged {
+            temperature_mc: 95_000,
+        };
+        bus.publish(engaged.clone());
+
+        assert_eq!(
+            first.try_iter().collect::<Vec<_>>(),
+            [applied, engaged.clone()]
+        );
+        assert_eq!(second.try_iter().collect::<Vec<_>>(), [engaged]);
+
+        // a dropped subscriber is forgotten on the next publish
+        drop(first);
+        bus.publish(Event::ThermalReleased {
+            temperature_mc: 80_000,
+        });
+        assert_eq!(bus.subscribers.len(), 1);
+    }
+}
@@ -0,0 +1,46 @@
+use libcpufreq::FeatureSet;
+
+use crate::fs::Fs;
+use crate::server::{CPU_DIR, PLATFORM_PROFILE, PSTATE_STATUS, attr_path};
+
+/// probe which optional cpufreq files exist on cpu0. files that cannot be checked count as missing
+pub(crate) fn detect_features<F: Fs>(fs: &F) -> FeatureSet {
+    let exists = |path: &str| fs.exists(path).unwrap_or(false);
+    let cpu0 = |attr: &str| exists(&attr_path(0, attr));
+    FeatureSet {
+        energy_performance_preference: cpu0("energy_performance_preference"),
+        base_frequency: cpu0("base_frequency"),
+        scaling_setspeed: cpu0("scaling_setspeed"),
+        available_frequencies: cpu0("scaling_available_frequencies"),
+        boost: exists(&format!("{CPU_DIR}/cpufreq/boost"))
+            || exists(&format!("{CPU_DIR}/intel_pstate/no_turbo")),
+        stats: cpu0("stats/total_trans"),
+        intel_pstate: exists(PSTATE_STATUS),
+        platform_profile: exists(PLATFORM_PROFILE),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::TestFs;
+
+    #[test]
+    fn detects_the_files_of_a_minimal_fixture() {
+        let fs = TestFs::new(&[
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+            "/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference",
+            "/sys/devices/system/cpu/cpu0/cpufreq/stats/total_trans",
+            "/sys/devices/system/cpu/intel_pstate/no_turbo",
+            "/sys/devices/system/cpu/intel_pstate/status",
+        ]);
+        let expected = FeatureSet {
+            energy_performance_preference: true,
+            boost: true,
+            stats: true,
+            intel_pstate: true,
+            ..Default::default()
+        };
+        assert_eq!(detect_features(&fs), expected);
+    }
+}
@@ -1,9 +1,13 @@
-use std::io::{self, ErrorKind};
+use std::convert::Infallible;
+use std::io::{self, ErrorKind, Read, Write};
+#[cfg(test)]
 use std::{
     collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use std::{
     fs,
     path::{Path, PathBuf},
-    sync::Arc,
 };
 
 macro_rules! mk_builder {
@@ -31,14 +35,62 @@ pub(crate) trait Fs {
     fn is_file(dirent: &Self::DirEnt) -> bool {
         !Self::is_dir(dirent)
     }
+    fn dirent_path(dirent: &Self::DirEnt) -> &Path;
+    /// every file below `path`, recursively.
+    ///
+    /// cycles are not detected. sysfs only forms cycles through symlinks, so this relies on
+    /// [Fs::is_dir] being false for symlinks (as it is for [fs::FileType::is_dir])
+    // kept for topology scanning, nothing outside the tests walks sysfs yet
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn walk(&self, path: &str) -> io::Result<Vec<Self::DirEnt>> {
+        let mut files = Vec::new();
+        for dirent in self.dir(path)? {
+            if Self::is_dir(&dirent) {
+                let dir = Self::dirent_path(&dirent)
+                    .to_str()
+                    .ok_or_else(|| io::Error::from(ErrorKind::InvalidData))?;
+                files.extend(self.walk(dir)?);
+            } else {
+                files.push(dirent);
+            }
+        }
+        Ok(files)
+    }
 }
 
 // Vertical illumination for first one because light cant penetrate the leaf
 
+/// the real filesystem, for sysfs on linux
+#[derive(Debug, Default, Clone)]
+pub(crate) struct SysFs;
+
 #[derive(Debug)]
+pub(crate) struct SysDirEnt {
+    path: PathBuf,
+    /// false for symlinks, see [Fs::walk]
+    is_dir: bool,
+}
+
+/// an [Fs] that fails every operation with [ErrorKind::Unsupported], so the daemon builds and runs
+/// on platforms without cpufreq, eg for development on macOS or Windows
+#[derive(Debug, Default, Clone)]
+// built everywhere so that it keeps compiling, linux only uses it in tests
+#[cfg_attr(all(target_os = "linux", not(test)), allow(dead_code))]
+pub(crate) struct StubFs;
+
+/// the [Fs] for the platform being built for
+#[cfg(target_os = "linux")]
+pub(crate) type PlatformFs = SysFs;
+/// the [Fs] for the platform being built for
+#[cfg(not(target_os = "linux"))]
+pub(crate) type PlatformFs = StubFs;
+
+#[cfg(test)]
+#[derive(Debug, Clone)]
 /// no directories allowed, only absolute file paths
 pub(crate) struct TestFs(pub(crate) HashMap<Arc<Path>, String>);
 
+#[cfg(test)]
 #[derive(Debug)]
 pub(crate) struct TestFile {
     path: Arc<Path>,
@@ -66,17 +118,95 @@ impl From<OpenOptions> for fs::OpenOptions {
     }
 }
 
+#[cfg(test)]
 #[derive(Debug, PartialEq, Hash, Eq)]
 pub(crate) enum TestDirEnt {
     File(Arc<Path>),
     Dir(Arc<Path>),
 }
 
+impl Fs for SysFs {
+    type File = fs::File;
+    type DirEnt = SysDirEnt;
+
+    fn exists(&self, path: &str) -> io::Result<bool> {
+        Path::new(path).try_exists()
+    }
+
+    fn open(&self, options: OpenOptions, path: &str) -> io::Result<Self::File> {
+        fs::OpenOptions::from(options).open(path)
+    }
+
+    fn read_to_string(&self, f: &mut Self::File) -> io::Result<String> {
+        let mut content = String::new();
+        f.read_to_string(&mut content)?;
+        Ok(content)
+    }
+
+    fn write_to(&mut self, path: &mut Self::File, content: &[u8]) -> io::Result<()> {
+        path.write_all(content)
+    }
+
+    fn dir(&self, path: &str) -> io::Result<Vec<Self::DirEnt>> {
+        fs::read_dir(path)?
+            .map(|dirent| {
+                let dirent = dirent?;
+                Ok(SysDirEnt {
+                    path: dirent.path(),
+                    is_dir: dirent.file_type()?.is_dir(),
+                })
+            })
+            .collect()
+    }
+
+    fn is_dir(dirent: &Self::DirEnt) -> bool {
+        dirent.is_dir
+    }
+
+    fn dirent_path(dirent: &Self::DirEnt) -> &Path {
+        &dirent.path
+    }
+}
+
+impl Fs for StubFs {
+    type File = Infallible;
+    type DirEnt = Infallible;
+
+    fn exists(&self, _path: &str) -> io::Result<bool> {
+        Err(io::Error::from(ErrorKind::Unsupported))
+    }
+
+    fn open(&self, _options: OpenOptions, _path: &str) -> io::Result<Self::File> {
+        Err(io::Error::from(ErrorKind::Unsupported))
+    }
+
+    fn read_to_string(&self, f: &mut Self::File) -> io::Result<String> {
+        match *f {}
+    }
+
+    fn write_to(&mut self, path: &mut Self::File, _content: &[u8]) -> io::Result<()> {
+        match *path {}
+    }
+
+    fn dir(&self, _path: &str) -> io::Result<Vec<Self::DirEnt>> {
+        Err(io::Error::from(ErrorKind::Unsupported))
+    }
+
+    fn is_dir(dirent: &Self::DirEnt) -> bool {
+        match *dirent {}
+    }
+
+    fn dirent_path(dirent: &Self::DirEnt) -> &Path {
+        match *dirent {}
+    }
+}
+
+#[cfg(test)]
 impl Fs for TestFs {
     type File = TestFile;
     type DirEnt = TestDirEnt;
 
-    fn open<'a, 'b>(&self, options: OpenOptions, path: &'a str) -> io::Result<Self::File> {
+    fn open(&self, options: OpenOptions, path: &str) -> io::Result<Self::File> {
         if self.exists(path)? || options.create {
             return Ok(Self::File {
                 path: Path::new(path).into(),
@@ -109,8 +239,15 @@ impl Fs for TestFs {
             return Err(io::Error::from(ErrorKind::NotFound));
         }
 
-        //TODO?: implement errors on incorrect content (eg if the
-        // /sys/devices/system/cpu/cpuX/cpufreq/scaling_available_(frequencies|governors) does not allow the operation)
+        //TODO?: implement errors on incorrect frequencies (eg if
+        // /sys/devices/system/cpu/cpuX/cpufreq/scaling_available_frequencies does not allow the operation)
+        self.validate_write(&path.path, &content)?;
+        if path.options.append {
+            if let Some(existing) = self.0.get_mut(&path.path) {
+                existing.push_str(&content);
+            }
+            return Ok(());
+        }
         self.0.insert(path.path.clone(), content);
         Ok(())
     }
@@ -141,8 +278,15 @@ impl Fs for TestFs {
     fn is_dir(dirent: &Self::DirEnt) -> bool {
         matches!(dirent, Self::DirEnt::Dir(_))
     }
+
+    fn dirent_path(dirent: &Self::DirEnt) -> &Path {
+        match dirent {
+            Self::DirEnt::File(path) | Self::DirEnt::Dir(path) => path,
+        }
+    }
 }
 
+#[cfg(test)]
 impl TestFs {
     fn _dir(&self, path: Arc<Path>) -> io::Result<Vec<TestDirEnt>> {
         if self._exists(path.clone())? {
@@ -156,7 +300,7 @@ impl TestFs {
             .map(|(p, _)| p.clone())
             .map(|p| {
                 // p is guaranteed to have more elements than path
-                if let Some(_second_after_path) = p.iter().skip(len_path + 1).next() {
+                if let Some(_second_after_path) = p.iter().nth(len_path + 1) {
                     // we are dealing with a dir
                     return TestDirEnt::Dir(Arc::from(
                         p.components()
@@ -166,7 +310,7 @@ impl TestFs {
                     ));
                 }
                 // we are dealing with a file
-                return TestDirEnt::File(p.clone());
+                TestDirEnt::File(p.clone())
             })
             .collect::<HashSet<_>>()
             .into_iter()
@@ -191,8 +335,89 @@ impl TestFs {
         }
     }
 
+    /// reject content the kernel would reject with EINVAL: a `scaling_governor` write must name a
+    /// governor listed in the sibling `scaling_available_governors`, if there is one
+    fn validate_write(&self, path: &Path, content: &str) -> io::Result<()> {
+        if path
+            .file_name()
+            .is_none_or(|name| name != "scaling_governor")
+        {
+            return Ok(());
+        }
+        let available = path.with_file_name("scaling_available_governors");
+        match self.0.get(available.as_path()) {
+            Some(available) if !available.split_whitespace().any(|g| g == content.trim()) => {
+                Err(io::Error::from(ErrorKind::InvalidInput))
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn _exists(&self, path: Arc<Path>) -> io::Result<bool> {
         Ok(self.0.contains_key(&*path))
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dirent_path_gives_the_path_of_every_entry() {
+        let fs = TestFs::new(&[
+            "/sys/devices/system/cpu/present",
+            "/sys/devices/system/cpu/cpu0/online",
+            "/sys/devices/system/cpu/cpu1/online",
+        ]);
+        let entries = fs.dir("/sys/devices/system/cpu").unwrap();
+        let mut paths: Vec<&Path> = entries.iter().map(TestFs::dirent_path).collect();
+        paths.sort_unstable();
+        assert_eq!(
+            paths,
+            [
+                Path::new("/sys/devices/system/cpu/cpu0"),
+                Path::new("/sys/devices/system/cpu/cpu1"),
+                Path::new("/sys/devices/system/cpu/present"),
+            ]
+        );
+    }
+
+    #[test]
+    fn walk_finds_every_file_below_a_directory() {
+        let files = [
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+            "/sys/devices/system/cpu/cpu0/online",
+            "/sys/devices/system/cpu/cpu0/topology/core_id",
+            "/sys/devices/system/cpu/cpu1/cpufreq/scaling_governor",
+            "/sys/devices/system/cpu/present",
+        ];
+        let mut fixture = TestFs::new(&files);
+        fixture
+            .0
+            .insert(Arc::from(Path::new("/proc/cpuinfo")), String::new());
+        let entries = fixture.walk("/sys/devices/system/cpu").unwrap();
+        assert!(entries.iter().all(TestFs::is_file));
+        let mut paths: Vec<&Path> = entries.iter().map(TestFs::dirent_path).collect();
+        paths.sort_unstable();
+        assert_eq!(paths, files.map(Path::new));
+    }
+
+    #[test]
+    fn writing_an_unavailable_governor_is_invalid_input() {
+        let governor = "/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor";
+        let mut fs = TestFs::new(&[governor]);
+        fs.0.insert(
+            Arc::from(Path::new(governor).with_file_name("scaling_available_governors")),
+            "performance powersave\n".into(),
+        );
+        let mut file = fs
+            .open(OpenOptions::default().write(true), governor)
+            .unwrap();
+        let error = fs.write_to(&mut file, b"schedutil").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert_eq!(fs.0[Path::new(governor)], "no content");
+
+        fs.write_to(&mut file, b"powersave\n").unwrap();
+        assert_eq!(fs.0[Path::new(governor)], "powersave\n");
+    }
+}
@@ -0,0 +1,104 @@
+use libcpufreq::{FrequencyReading, GetOptions, PerCpuInformation};
+
+/// everything the server knows about a core. richer than the wire type [PerCpuInformation] so the
+/// server can use fields without sending them; [CoreInfo::into_wire] picks what a client asked for.
+/// the default value describes an offline core
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CoreInfo {
+    pub(crate) governor: String,
+    pub(crate) frequency: FrequencyReading,
+    pub(crate) online: bool,
+    pub(crate) scaling_min_khz: Option<u64>,
+    pub(crate) scaling_max_khz: Option<u64>,
+    pub(crate) cpuinfo_min_khz: Option<u64>,
+    pub(crate) cpuinfo_max_khz: Option<u64>,
+    pub(crate) thread_siblings: Option<Vec<u8>>,
+    pub(crate) measured_khz: Option<u64>,
+    pub(crate) base_frequency_khz: Option<u64>,
+    pub(crate) time_in_state: Option<Vec<(u64, u64)>>,
+    pub(crate) frequency_step_khz: Option<u64>,
+    pub(crate) available_frequencies: Option<Vec<u64>>,
+}
+
+impl CoreInfo {
+    /// whether the core can be tuned: it is online and has cpufreq files. a core only known from
+    /// `/proc/cpuinfo` is online without a governor
+    pub(crate) fn tunable(&self) -> bool {
+        self.online && !self.governor.is_empty()
+    }
+
+    /// the current frequency in kHz, if known
+    pub(crate) fn khz(&self) -> Option<u64> {
+        self.frequency.khz()
+    }
+
+    /// the wire form, leaving out the fields `options` did not ask for
+    pub(crate) fn into_wire(self, options: &GetOptions) -> PerCpuInformation {
+        let frequency = if options.include_frequency {
+            self.frequency
+        } else {
+            FrequencyReading::Unavailable
+        };
+        PerCpuInformation {
+            governor: self.governor,
+            megahertz: frequency.megahertz(),
+            khz: frequency.khz(),
+            frequency,
+            online: self.online,
+            scaling_min_khz: self.scaling_min_khz,
+            scaling_max_khz: self.scaling_max_khz,
+            cpuinfo_min_khz: self.cpuinfo_min_khz,
+            cpuinfo_max_khz: self.cpuinfo_max_khz,
+            thread_siblings: self.thread_siblings,
+            measured_khz: self.measured_khz.filter(|_| options.include_measured),
+            base_frequency_khz: self.base_frequency_khz,
+            time_in_state: self.time_in_state.filter(|_| options.include_time_in_state),
+            frequency_step_khz: self.frequency_step_khz,
+            available_frequencies: self
+                .available_frequencies
+                .filter(|_| options.include_available_frequencies),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rich() -> CoreInfo {
+        CoreInfo {
+            governor: "schedutil".into(),
+            frequency: FrequencyReading::Known(2_400_000),
+            online: true,
+            measured_khz: Some(2_350_000),
+            time_in_state: Some(vec![(800_000, 100), (2_400_000, 50)]),
+            available_frequencies: Some(vec![2_400_000, 800_000]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn into_wire_omits_unrequested_fields() {
+        let wire = rich().into_wire(&GetOptions::default());
+        assert_eq!(wire.governor, "schedutil");
+        assert_eq!(wire.khz, Some(2_400_000));
+        assert_eq!(wire.megahertz, Some(2400));
+        assert_eq!(wire.measured_khz, None);
+        assert_eq!(wire.time_in_state, None);
+        assert_eq!(wire.available_frequencies, None);
+
+        let options = GetOptions {
+            include_measured: true,
+            include_frequency: false,
+            include_time_in_state: true,
+            include_available_frequencies: true,
+            collapse: false,
+        };
+        let wire = rich().into_wire(&options);
+        assert_eq!(wire.frequency, FrequencyReading::Unavailable);
+        assert_eq!(wire.megahertz, None);
+        assert_eq!(wire.measured_khz, Some(2_350_000));
+        assert_eq!(wire.time_in_state.map(|pairs| pairs.len()), Some(2));
+        assert_eq!(wire.available_frequencies, Some(vec![2_400_000, 800_000]));
+    }
+}
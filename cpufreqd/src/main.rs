@@ -1,3 +1,90 @@
-fn main() {
-    println!("Hello, world!");
+mod competitors;
+mod connection;
+mod events;
+mod features;
+mod fs;
+mod info;
+mod server;
+mod state;
+mod watch;
+
+use std::io;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::process::ExitCode;
+use std::thread;
+
+use libcpufreq::{Features, accept_features};
+
+use crate::connection::serve_connection;
+use crate::fs::PlatformFs;
+use crate::server::Server;
+use crate::watch::MIN_POLL_INTERVAL;
+
+/// where clients connect
+const SOCKET_PATH: &str = "/run/cpufreqd.sock";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("cpufreqd: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> io::Result<()> {
+    let mut server = Server::new(PlatformFs::default());
+    if let Err(e) = server.probe_writes() {
+        eprintln!("cpufreqd: could not check whether writes take effect: {e}");
+    }
+
+    let mut boot = server.fork();
+    thread::spawn(move || {
+        if let Err(e) = boot.apply_boot_governors() {
+            eprintln!("cpufreqd: boot governors: {e}");
+        }
+    });
+    let mut maintenance = server.fork();
+    thread::spawn(move || {
+        loop {
+            let results = [
+                maintenance.expire_fallbacks(),
+                maintenance.thermal_guard(),
+                maintenance.enforce_max_freq_caps(),
+            ];
+            for e in results.into_iter().filter_map(Result::err) {
+                eprintln!("cpufreqd: {e}");
+            }
+            thread::sleep(MIN_POLL_INTERVAL);
+        }
+    });
+
+    // a socket left behind by a previous run would make bind fail
+    match std::fs::remove_file(SOCKET_PATH) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let listener = UnixListener::bind(SOCKET_PATH)?;
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("cpufreqd: accept: {e}");
+                continue;
+            }
+        };
+        let server = server.connect();
+        thread::spawn(move || {
+            if let Err(e) = handle_client(server, stream) {
+                eprintln!("cpufreqd: connection: {e}");
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle_client(server: Server<PlatformFs>, mut stream: UnixStream) -> io::Result<()> {
+    let features = accept_features(&mut stream, Features::supported())?;
+    serve_connection(server, features, &stream, &stream)
 }
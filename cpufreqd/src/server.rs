@@ -0,0 +1,1920 @@
+use std::cell::OnceCell;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::io::{self, ErrorKind};
+use std::time::Duration;
+
+use libcpufreq::{
+    Capabilities, Clock, CoreStats, CpuCores, DaemonConfig, FeatureSet, FrequencyReading,
+    GetOptions, Information, PackageInformation, PerCpuInformation, Profile, Request, Response,
+    ScalingType, ServerError, ServerResult, SubscriptionId, SystemClock, Topology, average_watts,
+    frequency_step, max_freq_for_thermal, normalize_governor_name, parse_cpu_list, parse_cpuinfo,
+    parse_time_in_state,
+};
+
+use crate::competitors::detect_competitors;
+use crate::events::Event;
+use crate::features::detect_features;
+use crate::fs::{Fs, OpenOptions};
+use crate::info::CoreInfo;
+use crate::state::{Fallback, SharedState};
+use crate::watch::{ChangeWatcher, DeltaWatcher, MIN_POLL_INTERVAL};
+
+pub(crate) const CPU_DIR: &str = "/sys/devices/system/cpu";
+pub(crate) const PLATFORM_PROFILE: &str = "/sys/firmware/acpi/platform_profile";
+pub(crate) const PLATFORM_PROFILE_CHOICES: &str = "/sys/firmware/acpi/platform_profile_choices";
+pub(crate) const PSTATE_STATUS: &str = "/sys/devices/system/cpu/intel_pstate/status";
+pub(crate) const PROC_STATUS: &str = "/proc/self/status";
+pub(crate) const PROC_CPUINFO: &str = "/proc/cpuinfo";
+pub(crate) const NMI_WATCHDOG: &str = "/proc/sys/kernel/nmi_watchdog";
+pub(crate) const POWERCAP_DIR: &str = "/sys/class/powercap";
+pub(crate) const THERMAL_DIR: &str = "/sys/class/thermal";
+pub(crate) const SMT_CONTROL: &str = "/sys/devices/system/cpu/smt/control";
+/// how long a [Response::ConfirmationRequired] token stays valid
+pub(crate) const CONFIRMATION_TIMEOUT_MS: u64 = 60_000;
+/// the pause between [ServerOptions::read_retries]
+pub(crate) const READ_RETRY_DELAY: Duration = Duration::from_millis(10);
+/// how long [Request::GetPower] samples the energy counters for
+pub(crate) const POWER_SAMPLE_WINDOW: Duration = Duration::from_millis(100);
+
+/// the cpufreq attributes the server reads, relative to `cpuN/cpufreq`
+pub(crate) const ATTRIBUTES: &[&str] = &[
+    "affected_cpus",
+    "related_cpus",
+    "base_frequency",
+    "bios_limit",
+    "cpuinfo_cur_freq",
+    "cpuinfo_max_freq",
+    "cpuinfo_min_freq",
+    "cpuinfo_transition_latency",
+    "energy_performance_available_preferences",
+    "energy_performance_preference",
+    "scaling_available_frequencies",
+    "scaling_available_governors",
+    "scaling_cur_freq",
+    "scaling_driver",
+    "scaling_governor",
+    "scaling_max_freq",
+    "scaling_min_freq",
+    "scaling_setspeed",
+];
+
+type Result<T> = ServerResult<T, io::Error>;
+
+/// behaviour of a [Server]
+#[derive(Debug, Clone)]
+pub(crate) struct ServerOptions {
+    /// read every governor back after writing it and fail with [ServerError::WriteNotApplied] if
+    /// it did not stick. some locked down systems silently ignore writes
+    pub(crate) verify_writes: bool,
+    /// the shortest time allowed between two governor changes of the same core. a change that
+    /// comes sooner is rejected with [Response::Deferred] rather than queued. [None] disables this
+    pub(crate) min_change_interval: Option<Duration>,
+    /// governors to use while the system boots and after it has booted, see
+    /// [Server::apply_boot_governors]
+    pub(crate) boot_governors: Option<BootGovernors>,
+    /// file that every mutating request is appended to before it is carried out, one line each.
+    /// a request that cannot be logged is refused. [None] disables logging
+    pub(crate) request_log: Option<String>,
+    /// how many times to retry reading a file that should exist but is missing, to ride out
+    /// hotplug races. only reads are retried. 0 disables retrying
+    pub(crate) read_retries: u32,
+    /// scan running processes for other frequency managers and report them in [Capabilities]
+    pub(crate) detect_competitors: bool,
+    /// fail a [Request::Set] that selects missing or offline cores, before anything is written,
+    /// instead of leaving them out
+    pub(crate) strict_cores: bool,
+    /// the profiles [Request::ApplyProfile] can apply, by name, eg loaded from the config file
+    pub(crate) profiles: HashMap<String, Profile>,
+    /// limit `scaling_max_freq` while the cpu is hot, see [Server::thermal_guard]. [None] disables
+    /// this
+    pub(crate) thermal_guard: Option<ThermalGuard>,
+    /// keep `scaling_max_freq` at or below a cap even when something else raises it, see
+    /// [Server::enforce_max_freq_caps]. [None] disables this
+    pub(crate) max_freq_caps: Option<MaxFreqCaps>,
+}
+
+/// a governor for boot, eg `performance` for a fast init, and one to switch to once booted
+#[derive(Debug, Clone)]
+pub(crate) struct BootGovernors {
+    pub(crate) boot: String,
+    pub(crate) runtime: String,
+    /// how long after startup to switch from `boot` to `runtime`
+    pub(crate) delay: Duration,
+}
+
+/// the temperature [Server::thermal_guard] keeps the cpu under.
+///
+/// the guard engages once the temperature reaches `ceiling_mc` and only lets go once it has fallen
+/// to `hysteresis_mc` below it. a single threshold would restore the full frequency as soon as the
+/// cpu cooled by a fraction of a degree, heat it straight back up and oscillate
+#[derive(Debug, Clone)]
+pub(crate) struct ThermalGuard {
+    /// in millidegrees Celsius, like `thermal_zone*/temp`
+    pub(crate) ceiling_mc: u64,
+    /// in millidegrees Celsius
+    pub(crate) hysteresis_mc: u64,
+    /// how often the temperature is checked
+    pub(crate) interval: Duration,
+}
+
+/// per-core `scaling_max_freq` caps that [Server::enforce_max_freq_caps] keeps in place
+#[derive(Debug, Clone)]
+pub(crate) struct MaxFreqCaps {
+    /// the highest `scaling_max_freq` allowed for each core, in kHz
+    pub(crate) caps: HashMap<u8, u64>,
+    /// how often the caps are checked
+    pub(crate) interval: Duration,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            verify_writes: true,
+            min_change_interval: None,
+            boot_governors: None,
+            request_log: None,
+            read_retries: 0,
+            detect_competitors: false,
+            strict_cores: false,
+            profiles: HashMap::new(),
+            thermal_guard: None,
+            max_freq_caps: None,
+        }
+    }
+}
+
+// the daemon runs with the default options until it reads a config file, only tests set them
+#[cfg(test)]
+impl ServerOptions {
+    pub(crate) fn verify_writes(self, val: bool) -> Self {
+        Self {
+            verify_writes: val,
+            ..self
+        }
+    }
+
+    pub(crate) fn min_change_interval(self, val: Option<Duration>) -> Self {
+        Self {
+            min_change_interval: val,
+            ..self
+        }
+    }
+
+    pub(crate) fn boot_governors(self, val: Option<BootGovernors>) -> Self {
+        Self {
+            boot_governors: val,
+            ..self
+        }
+    }
+
+    pub(crate) fn request_log(self, val: Option<String>) -> Self {
+        Self {
+            request_log: val,
+            ..self
+        }
+    }
+
+    pub(crate) fn read_retries(self, val: u32) -> Self {
+        Self {
+            read_retries: val,
+            ..self
+        }
+    }
+
+    pub(crate) fn strict_cores(self, val: bool) -> Self {
+        Self {
+            strict_cores: val,
+            ..self
+        }
+    }
+
+    pub(crate) fn profiles(self, val: HashMap<String, Profile>) -> Self {
+        Self {
+            profiles: val,
+            ..self
+        }
+    }
+
+    pub(crate) fn thermal_guard(self, val: Option<ThermalGuard>) -> Self {
+        Self {
+            thermal_guard: val,
+            ..self
+        }
+    }
+
+    pub(crate) fn max_freq_caps(self, val: Option<MaxFreqCaps>) -> Self {
+        Self {
+            max_freq_caps: val,
+            ..self
+        }
+    }
+}
+
+impl ServerOptions {
+    /// the options as reported by [Request::GetConfig]. profiles are listed by name only
+    pub(crate) fn to_config(&self) -> DaemonConfig {
+        let mut profiles: Vec<String> = self.profiles.keys().cloned().collect();
+        profiles.sort_unstable();
+        DaemonConfig {
+            verify_writes: self.verify_writes,
+            min_change_interval: self.min_change_interval,
+            boot_governors: self
+                .boot_governors
+                .as_ref()
+                .map(|boot| (boot.boot.clone(), boot.runtime.clone(), boot.delay)),
+            request_log: self.request_log.clone(),
+            read_retries: self.read_retries,
+            detect_competitors: self.detect_competitors,
+            strict_cores: self.strict_cores,
+            profiles,
+            thermal_guard: self
+                .thermal_guard
+                .as_ref()
+                .map(|guard| (guard.ceiling_mc, guard.hysteresis_mc, guard.interval)),
+            max_freq_caps: self.max_freq_caps.as_ref().map(|caps| {
+                let mut cores: Vec<(u8, u64)> =
+                    caps.caps.iter().map(|(core, khz)| (*core, *khz)).collect();
+                cores.sort_unstable();
+                (cores, caps.interval)
+            }),
+        }
+    }
+}
+
+/// handles [Request]s against the cpufreq files exposed through an [Fs]
+pub(crate) struct Server<F: Fs, C: Clock = SystemClock> {
+    fs: F,
+    options: ServerOptions,
+    clock: C,
+    state: SharedState,
+    /// detected on first use and kept for the lifetime of the server
+    features: OnceCell<FeatureSet>,
+    /// who is connected, eg `uid=1000`, for the request log
+    client: Option<String>,
+    /// the connection the server answers, only streams it started can be unsubscribed. see
+    /// [Server::connect]
+    connection: u64,
+}
+
+impl<F: Fs> Server<F> {
+    pub(crate) fn new(fs: F) -> Self {
+        Self::with_options(fs, ServerOptions::default())
+    }
+
+    pub(crate) fn with_options(fs: F, options: ServerOptions) -> Self {
+        Server::with_clock(fs, options, SystemClock)
+    }
+}
+
+impl<F: Fs, C: Clock> Server<F, C> {
+    pub(crate) fn with_clock(fs: F, options: ServerOptions, clock: C) -> Self {
+        Self {
+            fs,
+            options,
+            clock,
+            state: SharedState::default(),
+            features: OnceCell::new(),
+            client: None,
+            connection: 0,
+        }
+    }
+
+    /// identify the connected client in the request log
+    #[cfg(test)]
+    pub(crate) fn with_client(self, client: String) -> Self {
+        Self {
+            client: Some(client),
+            ..self
+        }
+    }
+
+    /// share `state` with other servers, see also [Server::fork]
+    #[cfg(test)]
+    pub(crate) fn with_state(self, state: SharedState) -> Self {
+        Self { state, ..self }
+    }
+
+    pub(crate) fn state(&self) -> &SharedState {
+        &self.state
+    }
+
+    /// another server for the same system and client sharing this one's state, eg to run a stream
+    /// on its own thread while the connection goes on answering requests
+    pub(crate) fn fork(&self) -> Self
+    where
+        F: Clone,
+        C: Clone,
+    {
+        Self {
+            fs: self.fs.clone(),
+            options: self.options.clone(),
+            clock: self.clock.clone(),
+            state: self.state.clone(),
+            features: self.features.clone(),
+            client: self.client.clone(),
+            connection: self.connection,
+        }
+    }
+
+    /// a [Server::fork] for a new client connection. the streams of each connection are its own,
+    /// see [Request::Unsubscribe]
+    pub(crate) fn connect(&self) -> Self
+    where
+        F: Clone,
+        C: Clone,
+    {
+        let mut state = self.state.lock();
+        state.last_connection += 1;
+        Self {
+            connection: state.last_connection,
+            ..self.fork()
+        }
+    }
+
+    /// apply [ServerOptions::boot_governors] to every tunable core: the boot governor now and the
+    /// runtime governor once the delay has passed on the clock. this blocks for the delay, so the
+    /// startup path runs it alongside serving requests. does nothing if no boot governors are
+    /// configured
+    pub(crate) fn apply_boot_governors(&mut self) -> Result<()> {
+        let Some(governors) = self.options.boot_governors.clone() else {
+            return Ok(());
+        };
+        let mut cores = Vec::new();
+        for core in self.resolve(&CpuCores::All)? {
+            if self.core_info(core, &GetOptions::default())?.tunable() {
+                cores.push(core);
+            }
+        }
+        for core in &cores {
+            self.apply(*core, &ScalingType::Preset(governors.boot.clone()))?;
+        }
+        self.clock.sleep(governors.delay);
+        for core in &cores {
+            self.apply(*core, &ScalingType::Preset(governors.runtime.clone()))?;
+        }
+        Ok(())
+    }
+
+    /// check once at startup whether writes take effect, for [Capabilities::writes_effective].
+    /// cpu0's current governor is written back to it and read again, so the probe changes nothing
+    /// even if it fails part way. this catches firmware that rejects the write or puts back a
+    /// governor of its own, a write that is silently dropped still reads back as applied
+    pub(crate) fn probe_writes(&mut self) -> Result<bool> {
+        let current = self.read_attr(0, "scaling_governor")?;
+        self.write_attr(0, "scaling_governor", &current)?;
+        let effective = self.read_attr(0, "scaling_governor")? == current;
+        self.state.lock().writes_effective = Some(effective);
+        Ok(effective)
+    }
+
+    /// revert every core whose [ScalingType::WithFallback] deadline has passed. runs before every
+    /// request, the daemon should also call it periodically so reverts happen on time while idle.
+    /// cores that fail to revert stay pending and the first error is returned
+    pub(crate) fn expire_fallbacks(&mut self) -> Result<()> {
+        let now = self.clock.monotonic_ms();
+        let expired: Vec<(u8, String)> = self
+            .state
+            .lock()
+            .fallbacks
+            .iter()
+            .filter(|(_, fallback)| fallback.deadline_ms <= now)
+            .map(|(core, fallback)| (*core, fallback.governor.clone()))
+            .collect();
+
+        let mut first_error = None;
+        for (core, governor) in expired {
+            match self.apply(core, &ScalingType::Preset(governor)) {
+                Ok(()) => {
+                    self.state.lock().fallbacks.remove(&core);
+                }
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// enforce [ServerOptions::thermal_guard]. the daemon calls this periodically, it does nothing
+    /// until the guard's interval has passed on the clock since the last check.
+    ///
+    /// while engaged every tunable core's `scaling_max_freq` follows [max_freq_for_thermal] for the
+    /// current temperature, never above what it was before the guard engaged. once the cpu has
+    /// cooled to the hysteresis below the ceiling those previous limits are written back. limits
+    /// set by clients while engaged are overwritten. does nothing without a cpu thermal zone
+    pub(crate) fn thermal_guard(&mut self) -> Result<()> {
+        let Some(guard) = self.options.thermal_guard.clone() else {
+            return Ok(());
+        };
+        let now = self.clock.monotonic_ms();
+        {
+            let mut state = self.state.lock();
+            let interval_ms = guard.interval.as_millis() as u64;
+            if state
+                .thermal_checked_ms
+                .is_some_and(|checked| now < checked + interval_ms)
+            {
+                return Ok(());
+            }
+            state.thermal_checked_ms = Some(now);
+        }
+        let Some(temperature) = self.cpu_temperature() else {
+            return Ok(());
+        };
+
+        let engaged = !self.state.lock().thermal_limits.is_empty();
+        let release_mc = guard.ceiling_mc.saturating_sub(guard.hysteresis_mc);
+        if temperature >= guard.ceiling_mc || (engaged && temperature > release_mc) {
+            if !engaged {
+                let engaged = Event::ThermalEngaged {
+                    temperature_mc: temperature,
+                };
+                self.state.lock().events.publish(engaged);
+            }
+            for core in self.resolve(&CpuCores::All)? {
+                if !self.core_info(core, &GetOptions::default())?.tunable() {
+                    continue;
+                }
+                let previous = self.state.lock().thermal_limits.get(&core).copied();
+                let previous = match previous {
+                    Some(previous) => previous,
+                    None => {
+                        let previous = self.read_attr_khz(core, "scaling_max_freq")?;
+                        self.state.lock().thermal_limits.insert(core, previous);
+                        previous
+                    }
+                };
+                let limit = max_freq_for_thermal(
+                    temperature,
+                    guard.ceiling_mc,
+                    self.read_attr_khz(core, "cpuinfo_min_freq")?,
+                    self.read_attr_khz(core, "cpuinfo_max_freq")?,
+                );
+                self.write_attr(core, "scaling_max_freq", &limit.min(previous).to_string())?;
+            }
+            return Ok(());
+        }
+        if engaged {
+            let limits = {
+                let mut state = self.state.lock();
+                let released = Event::ThermalReleased {
+                    temperature_mc: temperature,
+                };
+                state.events.publish(released);
+                std::mem::take(&mut state.thermal_limits)
+            };
+            let mut first_error = None;
+            for (core, previous) in limits {
+                if let Err(e) = self.write_attr(core, "scaling_max_freq", &previous.to_string()) {
+                    first_error.get_or_insert(e);
+                }
+            }
+            return first_error.map_or(Ok(()), Err);
+        }
+        Ok(())
+    }
+
+    /// enforce [ServerOptions::max_freq_caps]: lower the `scaling_max_freq` of every tunable capped
+    /// core that is above its cap back to the cap, undoing other tools raising it. a maximum
+    /// below the cap is left alone. the daemon calls this periodically, it does nothing until the
+    /// interval has passed on the clock since the last check. every core is attempted and the
+    /// first error is returned afterwards
+    pub(crate) fn enforce_max_freq_caps(&mut self) -> Result<()> {
+        let Some(caps) = self.options.max_freq_caps.clone() else {
+            return Ok(());
+        };
+        let now = self.clock.monotonic_ms();
+        {
+            let mut state = self.state.lock();
+            let interval_ms = caps.interval.as_millis() as u64;
+            if state
+                .caps_checked_ms
+                .is_some_and(|checked| now < checked + interval_ms)
+            {
+                return Ok(());
+            }
+            state.caps_checked_ms = Some(now);
+        }
+
+        let mut first_error = None;
+        for (core, cap) in caps.caps {
+            let result = self
+                .core_info(core, &GetOptions::default())
+                .and_then(|info| match info.scaling_max_khz {
+                    Some(max) if info.tunable() && max > cap => {
+                        self.write_attr(core, "scaling_max_freq", &cap.to_string())
+                    }
+                    _ => Ok(()),
+                });
+            if let Err(e) = result {
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// the hottest cpu thermal zone in millidegrees Celsius: `x86_pkg_temp` or any zone whose
+    /// `type` mentions the cpu. [None] if there is none or none can be read
+    fn cpu_temperature(&self) -> Option<u64> {
+        let dirents = self.fs.dir(THERMAL_DIR).ok()?;
+        dirents
+            .iter()
+            .filter(|dirent| F::is_dir(dirent))
+            .filter_map(|dirent| {
+                let zone = F::dirent_path(dirent).to_str()?;
+                let kind = self.read(&format!("{zone}/type")).ok()?;
+                if kind != "x86_pkg_temp" && !kind.contains("cpu") {
+                    return None;
+                }
+                self.read(&format!("{zone}/temp")).ok()?.parse().ok()
+            })
+            .max()
+    }
+
+    /// push every pending fallback's deadline out by its `after`
+    fn keepalive(&mut self) {
+        let now = self.clock.monotonic_ms();
+        for fallback in self.state.lock().fallbacks.values_mut() {
+            fallback.deadline_ms = now + fallback.after_ms;
+        }
+    }
+
+    /// answer `request`, passing every response to `emit`. streaming requests keep emitting until
+    /// `emit` returns false (the client went away) or an error has been emitted. they are given a
+    /// [SubscriptionId] unique to the server's state, announced with [Response::Subscribed], and
+    /// every later frame is wrapped in a [Response::Frame] with it and the wall clock time from
+    /// [Clock::now_ms] at which it was sent
+    pub(crate) fn serve(
+        &mut self,
+        request: Request,
+        mut emit: impl FnMut(Response<io::Error>) -> bool,
+    ) where
+        C: Clone,
+    {
+        if !request.is_streaming() {
+            emit(self.handle(request));
+            return;
+        }
+        let id = self.new_subscription();
+        self.serve_stream(id, request, emit);
+    }
+
+    /// a fresh [SubscriptionId], unique to the server's state and already active, for
+    /// [Server::serve_stream]
+    pub(crate) fn new_subscription(&self) -> SubscriptionId {
+        let mut state = self.state.lock();
+        state.last_subscription += 1;
+        let id = SubscriptionId(state.last_subscription);
+        state.subscriptions.insert(id, self.connection);
+        id
+    }
+
+    /// stream the streaming `request` as `id`, see [Server::serve]. a caller that needs the id
+    /// before the stream starts, eg to stop it from another thread, gets it from
+    /// [Server::new_subscription]
+    pub(crate) fn serve_stream(
+        &mut self,
+        id: SubscriptionId,
+        request: Request,
+        mut emit: impl FnMut(Response<io::Error>) -> bool,
+    ) where
+        C: Clone,
+    {
+        if emit(Response::Subscribed(id)) {
+            // streams also check at every poll, this catches an unsubscribe during one
+            let (state, clock) = (self.state.clone(), self.clock.clone());
+            self.stream(id, request, |frame| {
+                state.lock().subscriptions.contains_key(&id)
+                    && emit(Response::Frame {
+                        id,
+                        timestamp_ms: clock.now_ms(),
+                        frame: Box::new(frame),
+                    })
+            });
+        }
+        self.state.lock().subscriptions.remove(&id);
+    }
+
+    /// whether the stream `id` is still active, ie it has not been cancelled with
+    /// [Request::Unsubscribe]
+    fn subscribed(&self, id: SubscriptionId) -> bool {
+        self.state.lock().subscriptions.contains_key(&id)
+    }
+
+    /// the frames of the streaming request `id`, see [Server::serve]. the stream ends at its next
+    /// poll once `id` is unsubscribed
+    fn stream(
+        &mut self,
+        id: SubscriptionId,
+        request: Request,
+        mut emit: impl FnMut(Response<io::Error>) -> bool,
+    ) {
+        match request {
+            Request::WatchChanges(cores) => {
+                let mut watcher = ChangeWatcher::default();
+                while self.subscribed(id) {
+                    match self.get_cached(&cores) {
+                        Ok(info) => {
+                            if let Some(info) = watcher.changed(info)
+                                && !emit(Response::Information(info))
+                            {
+                                return;
+                            }
+                        }
+                        Err(e) => {
+                            emit(Response::Error(e));
+                            return;
+                        }
+                    }
+                    self.clock.sleep(MIN_POLL_INTERVAL);
+                }
+            }
+            Request::Subscribe(selections) if !selections.is_empty() => {
+                self.subscribe(id, &selections, emit)
+            }
+            Request::WatchExternal(cores) => self.watch_external(id, &cores, emit),
+            Request::SubscribeDelta(cores, threshold_mhz) => {
+                let mut watcher = DeltaWatcher::new(threshold_mhz);
+                while self.subscribed(id) {
+                    match self.get_cached(&cores) {
+                        Ok(info) => {
+                            if let Some(info) = watcher.changed(info)
+                                && !emit(Response::Information(info))
+                            {
+                                return;
+                            }
+                        }
+                        Err(e) => {
+                            emit(Response::Error(e));
+                            return;
+                        }
+                    }
+                    self.clock.sleep(MIN_POLL_INTERVAL);
+                }
+            }
+            request => {
+                emit(self.handle(request));
+            }
+        }
+    }
+
+    /// see [Request::WatchExternal]. the server's own changes are learned from the
+    /// [Event::GovernorApplied] events on the [EventBus](crate::events::EventBus): a change to the
+    /// governor the server last applied to a core since the stream started is taken to be the
+    /// server's own
+    fn watch_external(
+        &mut self,
+        id: SubscriptionId,
+        cores: &CpuCores,
+        mut emit: impl FnMut(Response<io::Error>) -> bool,
+    ) {
+        let events = self.state.lock().events.subscribe();
+        let mut applied = HashMap::new();
+        let mut last = match self.get_governor(cores) {
+            Ok(governors) => governors,
+            Err(e) => {
+                emit(Response::Error(e));
+                return;
+            }
+        };
+        if !emit(Response::Governors(last.clone())) {
+            return;
+        }
+        loop {
+            self.clock.sleep(MIN_POLL_INTERVAL);
+            if !self.subscribed(id) {
+                return;
+            }
+            let governors = match self.get_governor(cores) {
+                Ok(governors) => governors,
+                Err(e) => {
+                    emit(Response::Error(e));
+                    return;
+                }
+            };
+            // drained after reading, so that a write the read saw has normally been published
+            for event in events.try_iter() {
+                if let Event::GovernorApplied { core, governor } = event {
+                    applied.insert(core, governor);
+                }
+            }
+            let mut changes: Vec<_> = governors
+                .iter()
+                .filter_map(|(core, current)| {
+                    let previous = last.get(core).filter(|previous| *previous != current)?;
+                    (applied.get(core) != Some(current))
+                        .then(|| (*core, previous.clone(), current.clone()))
+                })
+                .collect();
+            changes.sort_unstable();
+            for (core, previous, current) in changes {
+                let event = Event::ExternalChange {
+                    core,
+                    previous: previous.clone(),
+                    current: current.clone(),
+                };
+                self.state.lock().events.publish(event);
+                let change = Response::ExternalChange {
+                    core,
+                    previous,
+                    current,
+                };
+                if !emit(change) {
+                    return;
+                }
+            }
+            last = governors;
+        }
+    }
+
+    /// see [Request::Subscribe]. selections that fall behind skip the frames they missed rather
+    /// than sending them in a burst
+    fn subscribe(
+        &mut self,
+        subscription: SubscriptionId,
+        selections: &[(CpuCores, Duration)],
+        mut emit: impl FnMut(Response<io::Error>) -> bool,
+    ) {
+        let intervals: Vec<u64> = selections
+            .iter()
+            .map(|(_, interval)| (*interval).max(MIN_POLL_INTERVAL).as_millis() as u64)
+            .collect();
+        let mut due = vec![self.clock.monotonic_ms(); selections.len()];
+        while self.subscribed(subscription) {
+            let now = self.clock.monotonic_ms();
+            for (id, (cores, _)) in selections.iter().enumerate() {
+                if due[id] > now {
+                    continue;
+                }
+                due[id] += intervals[id];
+                if due[id] <= now {
+                    due[id] = now + intervals[id];
+                }
+                let response = match self.get_cached(cores) {
+                    Ok(information) => Response::Selection {
+                        id: id as u32,
+                        information,
+                    },
+                    Err(e) => {
+                        emit(Response::Error(e));
+                        return;
+                    }
+                };
+                if !emit(response) {
+                    return;
+                }
+            }
+            // `due` is never empty, empty subscriptions are answered by `handle`
+            let next = due.iter().min().copied().unwrap_or(now);
+            self.clock.sleep(Duration::from_millis(
+                next.saturating_sub(self.clock.monotonic_ms()),
+            ));
+        }
+    }
+
+    /// answer a request with a single response. streaming requests are answered with their first
+    /// response, use [Server::serve] to stream them
+    pub(crate) fn handle(&mut self, request: Request) -> Response<io::Error> {
+        // a revert that fails is retried on the next request rather than failing this one
+        let _ = self.expire_fallbacks();
+        if request.is_mutating()
+            && let Err(e) = self.check_root().and_then(|_| self.log_request(&request))
+        {
+            return Response::Error(e);
+        }
+        let stale = request.is_mutating();
+        let redetect = matches!(request, Request::SetPstateStatus(_));
+        let response = match request {
+            Request::Get(cores)
+            | Request::WatchChanges(cores)
+            | Request::SubscribeDelta(cores, _) => self
+                .get(&cores, &GetOptions::default())
+                .map(Response::Information),
+            Request::GetWithOptions(cores, options) => self.get(&cores, &options).map(|info| {
+                Response::Information(if options.collapse {
+                    info.collapse()
+                } else {
+                    info
+                })
+            }),
+            Request::ResetAll => self.reset_all().map(|_| Response::Ack),
+            Request::GetAbove(khz) => self.get_above(khz).map(Response::Information),
+            Request::GetPower => self.get_power().map(Response::Power),
+            Request::SetOnline {
+                cores,
+                online,
+                confirmation,
+            } => self.set_online(&cores, online, confirmation),
+            Request::GetStats(cores) => self.get_stats(&cores).map(Response::Stats),
+            Request::ResetStats(cores) => self.reset_stats(&cores).map(|_| Response::Ack),
+            Request::Subscribe(selections) => match selections.into_iter().next() {
+                Some((cores, _)) => self
+                    .get(&cores, &GetOptions::default())
+                    .map(|information| Response::Selection { id: 0, information }),
+                None => Ok(Response::Ack),
+            },
+            Request::GetGovernor(cores) | Request::WatchExternal(cores) => {
+                self.get_governor(&cores).map(Response::Governors)
+            }
+            Request::ApplyProfile(name) => self.apply_profile(&name).map(|()| Response::Ack),
+            Request::ListProfiles => Ok(Response::Profiles(self.list_profiles())),
+            Request::GetConfig => Ok(Response::Config(self.options.to_config())),
+            Request::GetTunable { core, name } => self
+                .tunable_path(core, &name)
+                .and_then(|path| self.read(&path))
+                .map(Response::Tunable),
+            Request::SetTunable { core, name, value } => self
+                .tunable_path(core, &name)
+                .and_then(|path| self.write(&path, &value))
+                .map(|()| Response::Ack),
+            Request::ListTunables(core) => self.list_tunables(core).map(Response::Tunables),
+            Request::Keepalive => {
+                self.keepalive();
+                Ok(Response::Ack)
+            }
+            Request::Set(cores, scaling) => self.set(&cores, &scaling),
+            Request::List(cores) => self.list(&cores).map(Response::ScalingGovernors),
+            Request::SetWhere {
+                current_governor,
+                new,
+            } => self.set_where(&current_governor, &new),
+            Request::GetPackages => self.get_packages().map(Response::Packages),
+            Request::GetPlatformProfile => {
+                self.read(PLATFORM_PROFILE).map(Response::PlatformProfile)
+            }
+            Request::SetPlatformProfile(profile) => {
+                self.set_platform_profile(&profile).map(|_| Response::Ack)
+            }
+            Request::DumpCore(core) => Ok(Response::Dump(self.dump_core(core))),
+            Request::GetPstateStatus => self.pstate_status().map(Response::PstateStatus),
+            Request::SetPstateStatus(status) => {
+                self.set_pstate_status(&status).map(|_| Response::Ack)
+            }
+            Request::Capabilities => self.capabilities().map(Response::Capabilities),
+            Request::CoreCount => self.core_count().map(Response::CoreCount),
+            Request::SetSmt(enabled) => self.set_smt(enabled).map(|_| Response::Ack),
+            Request::Unsubscribe(id) => {
+                let mut state = self.state.lock();
+                // another connection's stream is as unknown as one that ended, ids are sequential
+                // and easy to guess
+                if state.subscriptions.get(&id) == Some(&self.connection) {
+                    state.subscriptions.remove(&id);
+                    Ok(Response::Ack)
+                } else {
+                    Err(ServerError::UnknownSubscription)
+                }
+            }
+            Request::Topology => self.topology().map(Response::Topology),
+            Request::GetNmiWatchdog => self.nmi_watchdog().map(Response::NmiWatchdog),
+            Request::SetNmiWatchdog(enabled) => {
+                self.set_nmi_watchdog(enabled).map(|_| Response::Ack)
+            }
+        };
+        // drop cached reads the request may have made stale, even if it failed part way through.
+        // switching the intel_pstate mode changes the governors and files the driver offers
+        if stale {
+            self.state.lock().invalidate_info(None);
+        }
+        if redetect {
+            self.features.take();
+        }
+        response.unwrap_or_else(Response::Error)
+    }
+
+    fn get(&self, cores: &CpuCores, options: &GetOptions) -> Result<Information> {
+        let mut table = HashMap::new();
+        for core in self.resolve(cores)? {
+            table.insert(core, self.core_info(core, options)?.into_wire(options));
+        }
+        Ok(Information::Table(table))
+    }
+
+    /// [Server::get] for streaming requests. reads are shared through the [SharedState] by every
+    /// stream polling in the same [MIN_POLL_INTERVAL] tick, so many subscribers cost about one
+    /// pass over sysfs per tick.
+    ///
+    /// the state is not locked while sysfs is read, so streams that miss the cache together may
+    /// each read the core. a read is only cached if nothing was written in the meantime, see
+    /// [ServerState::info_generation](crate::state::ServerState::info_generation)
+    fn get_cached(&self, cores: &CpuCores) -> Result<Information> {
+        let tick = self.clock.monotonic_ms() / MIN_POLL_INTERVAL.as_millis() as u64;
+        let options = GetOptions::default();
+        let mut table = HashMap::new();
+        for core in self.resolve(cores)? {
+            let (cached, generation) = {
+                let state = self.state.lock();
+                let cached = state
+                    .info_cache
+                    .get(&core)
+                    .filter(|(read_in, _)| *read_in == tick)
+                    .map(|(_, info)| info.clone());
+                (cached, state.info_generation)
+            };
+            let info = match cached {
+                Some(info) => info,
+                None => {
+                    let info = self.core_info(core, &options)?;
+                    let mut state = self.state.lock();
+                    if state.info_generation == generation {
+                        state.info_cache.insert(core, (tick, info.clone()));
+                    }
+                    info
+                }
+            };
+            table.insert(core, info.into_wire(&options));
+        }
+        Ok(Information::Table(table))
+    }
+
+    /// reads nothing but `scaling_governor`
+    fn get_governor(&self, cores: &CpuCores) -> Result<HashMap<u8, String>> {
+        let mut governors = HashMap::new();
+        for core in self.resolve(cores)? {
+            match self.read_attr(core, "scaling_governor") {
+                Ok(governor) => {
+                    governors.insert(core, governor);
+                }
+                Err(ServerError::Other(e)) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(governors)
+    }
+
+    fn get_above(&self, threshold_khz: u64) -> Result<Information> {
+        let mut table = HashMap::new();
+        for core in self.resolve(&CpuCores::All)? {
+            let info = self.core_info(core, &GetOptions::default())?;
+            if info.khz().is_some_and(|khz| khz > threshold_khz) {
+                table.insert(core, info.into_wire(&GetOptions::default()));
+            }
+        }
+        Ok(Information::Table(table))
+    }
+
+    /// unless [ServerOptions::strict_cores] is set, offline cores are dropped from the selection
+    /// instead of failing the request. when it is set, the request is all or nothing: a selected
+    /// core that is missing or offline fails it before any core is written. a write that fails
+    /// part way puts the cores already written back on their previous governors
+    fn set(&mut self, cores: &CpuCores, scaling: &ScalingType) -> Result<Response<io::Error>> {
+        let cores = match self.online_cores()? {
+            Some(online) if !self.options.strict_cores => cores.clamp_to_online(&online),
+            _ => cores.clone(),
+        };
+        let cores = self.resolve(&cores)?;
+        if self.options.strict_cores {
+            for core in &cores {
+                if !self.exists(&attr_path(*core, "scaling_governor"))? {
+                    return Err(ServerError::Other(io::Error::from(ErrorKind::NotFound)));
+                }
+            }
+        }
+        if let Some(retry_after_ms) = self.deferral(&cores) {
+            return Ok(Response::Deferred { retry_after_ms });
+        }
+        // read up front so that a failure part way has every governor to roll back to
+        let mut previous = HashMap::new();
+        for core in &cores {
+            previous.insert(*core, self.read_attr(*core, "scaling_governor")?);
+        }
+        for (i, core) in cores.iter().enumerate() {
+            if let Err(e) = self.apply(*core, scaling) {
+                self.roll_back(&cores[..i], &previous);
+                return Err(e);
+            }
+        }
+        Ok(Response::Applied { previous })
+    }
+
+    /// put `cores` back on their `previous` governors after a [Request::Set] failed part way.
+    /// best effort, the error that failed the request is the one reported
+    fn roll_back(&mut self, cores: &[u8], previous: &HashMap<u8, String>) {
+        for core in cores {
+            let _ = self.apply(*core, &ScalingType::Preset(previous[core].clone()));
+        }
+    }
+
+    /// fail with [ServerError::NotRoot] unless the effective uid in [PROC_STATUS] is 0. reads never
+    /// need root, so this is only checked for mutating requests. if the uid cannot be determined
+    /// the request goes ahead and the kernel has the final say
+    fn check_root(&self) -> Result<()> {
+        let Ok(status) = self.read(PROC_STATUS) else {
+            return Ok(());
+        };
+        // `Uid:` lists the real, effective, saved and filesystem uids
+        let euid = status
+            .lines()
+            .find_map(|line| line.strip_prefix("Uid:"))
+            .and_then(|uids| uids.split_whitespace().nth(1));
+        match euid {
+            Some(euid) if euid != "0" => Err(ServerError::NotRoot),
+            _ => Ok(()),
+        }
+    }
+
+    /// append `request` to [ServerOptions::request_log] as `<timestamp ms> <client> <request>`,
+    /// with `-` for an unknown client
+    fn log_request(&mut self, request: &Request) -> Result<()> {
+        let Some(path) = self.options.request_log.clone() else {
+            return Ok(());
+        };
+        let line = format!(
+            "{} {} {request:?}\n",
+            self.clock.now_ms(),
+            self.client.as_deref().unwrap_or("-")
+        );
+        let mut file = self
+            .fs
+            .open(
+                OpenOptions::default().write(true).append(true).create(true),
+                &path,
+            )
+            .map_err(fs_error)?;
+        self.fs
+            .write_to(&mut file, line.as_bytes())
+            .map_err(fs_error)
+    }
+
+    /// how long until every one of `cores` may change governor again under
+    /// [ServerOptions::min_change_interval], [None] if they all may change now
+    fn deferral(&self, cores: &[u8]) -> Option<u64> {
+        let interval = self.options.min_change_interval?.as_millis() as u64;
+        let now = self.clock.monotonic_ms();
+        let state = self.state.lock();
+        cores
+            .iter()
+            .filter_map(|core| state.last_change.get(core))
+            .map(|last| (last + interval).saturating_sub(now))
+            .filter(|wait| *wait > 0)
+            .max()
+    }
+
+    /// the governors available on every one of `cores`. on the `policyN` layout the governors are
+    /// read once per policy rather than once per core, cores outside any policy are read directly
+    fn list(&self, cores: &CpuCores) -> Result<Vec<String>> {
+        let mut remaining: BTreeSet<u8> = self.resolve(cores)?.into_iter().collect();
+        let mut available = Vec::new();
+        for (policy, members) in self.policies() {
+            let requested = members.iter().filter(|core| remaining.remove(core)).count();
+            if requested > 0 {
+                available.push(split_governors(
+                    &self.read(&format!("{policy}/scaling_available_governors"))?,
+                ));
+            }
+        }
+        for core in remaining {
+            available.push(self.available_governors(core)?);
+        }
+
+        let mut governors: Option<Vec<String>> = None;
+        for available in available {
+            governors = Some(match governors {
+                None => available,
+                Some(governors) => governors
+                    .into_iter()
+                    .filter(|governor| available.contains(governor))
+                    .collect(),
+            });
+        }
+        Ok(governors.unwrap_or_default())
+    }
+
+    /// every `cpufreq/policyN` directory with the cores it covers (`related_cpus`). empty on
+    /// kernels without the policy layout, unreadable policies are left out
+    fn policies(&self) -> Vec<(String, Vec<u8>)> {
+        let Ok(dirents) = self.fs.dir(&format!("{CPU_DIR}/cpufreq")) else {
+            return Vec::new();
+        };
+        dirents
+            .iter()
+            .filter(|dirent| F::is_dir(dirent))
+            .filter_map(|dirent| {
+                let path = F::dirent_path(dirent);
+                path.file_name()?.to_str()?.strip_prefix("policy")?;
+                let policy = path.to_str()?.to_owned();
+                let members = self.read(&format!("{policy}/related_cpus")).ok()?;
+                Some((policy, parse_cpu_list(&members)?))
+            })
+            .collect()
+    }
+
+    /// the directories that may hold the tunables of `core`'s current governor, most specific
+    /// first. drivers that keep a governor per policy put its tunables in `policyN/<governor>`,
+    /// the others share one `cpufreq/<governor>` directory
+    fn tunable_dirs(&self, core: u8) -> Result<Vec<String>> {
+        let governor = self.read_attr(core, "scaling_governor")?;
+        let policy = self
+            .policies()
+            .into_iter()
+            .find(|(_, members)| members.contains(&core));
+        Ok(policy
+            .map(|(policy, _)| format!("{policy}/{governor}"))
+            .into_iter()
+            .chain([format!("{CPU_DIR}/cpufreq/{governor}")])
+            .collect())
+    }
+
+    /// the file of tunable `name` of `core`'s current governor, see [Server::tunable_dirs]
+    fn tunable_path(&self, core: u8, name: &str) -> Result<String> {
+        if name.is_empty() || name.contains('/') || name == ".." {
+            return Err(ServerError::Other(io::Error::from(ErrorKind::InvalidInput)));
+        }
+        for dir in self.tunable_dirs(core)? {
+            let path = format!("{dir}/{name}");
+            if self.exists(&path)? {
+                return Ok(path);
+            }
+        }
+        Err(ServerError::Unsupported)
+    }
+
+    /// the name and value of every tunable of `core`'s current governor, sorted by name. files
+    /// that cannot be read are left out. empty if the governor has no tunables
+    fn list_tunables(&self, core: u8) -> Result<Vec<(String, String)>> {
+        for dir in self.tunable_dirs(core)? {
+            let Ok(dirents) = self.fs.dir(&dir) else {
+                continue;
+            };
+            let mut tunables: Vec<(String, String)> = dirents
+                .iter()
+                .filter(|dirent| F::is_file(dirent))
+                .filter_map(|dirent| {
+                    let path = F::dirent_path(dirent);
+                    let name = path.file_name()?.to_str()?.to_owned();
+                    Some((name, self.read(path.to_str()?).ok()?))
+                })
+                .collect();
+            if !tunables.is_empty() {
+                tunables.sort_unstable();
+                return Ok(tunables);
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    /// best-effort: every matching core is attempted and the first error is returned afterwards
+    fn set_where(
+        &mut self,
+        current_governor: &str,
+        new: &ScalingType,
+    ) -> Result<Response<io::Error>> {
+        let mut matching = Vec::new();
+        for core in self.resolve(&CpuCores::All)? {
+            let info = self.core_info(core, &GetOptions::default())?;
+            if info.tunable() && info.governor == current_governor {
+                matching.push(core);
+            }
+        }
+
+        if let Some(retry_after_ms) = self.deferral(&matching) {
+            return Ok(Response::Deferred { retry_after_ms });
+        }
+
+        let mut first_error = None;
+        for core in matching {
+            if let Err(e) = self.apply(core, new) {
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(Response::Ack), Err)
+    }
+
+    /// online cores grouped by `topology/physical_package_id`
+    fn get_packages(&self) -> Result<HashMap<u8, PackageInformation>> {
+        let mut packages: HashMap<u8, HashMap<u8, PerCpuInformation>> = HashMap::new();
+        for core in self.resolve(&CpuCores::All)? {
+            let info = self.core_info(core, &GetOptions::default())?;
+            if !info.online {
+                continue;
+            }
+            packages
+                .entry(self.package_of(core)?)
+                .or_default()
+                .insert(core, info.into_wire(&GetOptions::default()));
+        }
+
+        Ok(packages
+            .into_iter()
+            .map(|(package, cores)| {
+                let cores = Information::Table(cores);
+                let summary = PackageInformation {
+                    governors: cores.governors().into_iter().map(String::from).collect(),
+                    average_mhz: cores.average_mhz(),
+                    energy_uj: self.package_energy_uj(package),
+                };
+                (package, summary)
+            })
+            .collect())
+    }
+
+    /// see [Request::GetPower]. packages whose counter cannot be read or wrapped during the
+    /// window are left out, as are all packages if the clock went backwards
+    fn get_power(&self) -> Result<HashMap<u8, f64>> {
+        let mut packages = BTreeSet::new();
+        for core in self.resolve(&CpuCores::All)? {
+            if self.core_info(core, &GetOptions::default())?.online {
+                packages.insert(self.package_of(core)?);
+            }
+        }
+
+        let sample = |packages: &BTreeSet<u8>| -> HashMap<u8, u64> {
+            packages
+                .iter()
+                .filter_map(|package| Some((*package, self.package_energy_uj(*package)?)))
+                .collect()
+        };
+        let (start_ms, start) = (self.clock.monotonic_ms(), sample(&packages));
+        if start.is_empty() {
+            return Err(ServerError::Unsupported);
+        }
+        self.clock.sleep(POWER_SAMPLE_WINDOW);
+        let (end_ms, end) = (self.clock.monotonic_ms(), sample(&packages));
+
+        Ok(start
+            .into_iter()
+            .filter_map(|(package, start_uj)| {
+                let elapsed_ms = end_ms.checked_sub(start_ms)?;
+                let watts = average_watts(start_uj, *end.get(&package)?, elapsed_ms)?;
+                Some((package, watts))
+            })
+            .collect())
+    }
+
+    /// see [Request::Topology]. without an `online` cpu list the present cores that report as
+    /// online are used
+    fn topology(&self) -> Result<Topology> {
+        let mut present = self.present_cores()?;
+        present.sort_unstable();
+        let mut online = match self.online_cores()? {
+            Some(online) => online
+                .into_iter()
+                .filter(|core| present.contains(core))
+                .collect(),
+            None => {
+                let mut online = Vec::new();
+                for core in &present {
+                    if self.core_info(*core, &GetOptions::default())?.online {
+                        online.push(*core);
+                    }
+                }
+                online
+            }
+        };
+        online.sort_unstable();
+
+        let mut packages: HashMap<u8, Vec<u8>> = HashMap::new();
+        for core in &online {
+            packages
+                .entry(self.package_of(*core)?)
+                .or_default()
+                .push(*core);
+        }
+        let mut clusters: Vec<Vec<u8>> = self
+            .policies()
+            .into_iter()
+            .map(|(_, mut members)| {
+                members.sort_unstable();
+                members
+            })
+            .collect();
+        clusters.sort_unstable();
+
+        Ok(Topology {
+            present,
+            online,
+            packages,
+            clusters,
+        })
+    }
+
+    /// the physical package (socket) `core` belongs to
+    fn package_of(&self, core: u8) -> Result<u8> {
+        self.read_expected(&format!("{CPU_DIR}/cpu{core}/topology/physical_package_id"))?
+            .parse()
+            .map_err(|_| ServerError::Other(io::Error::from(ErrorKind::InvalidData)))
+    }
+
+    /// the RAPL energy counter of `package`, [None] without powercap support (eg in most VMs) or
+    /// if it is unreadable. zones are numbered in the order the kernel registered them, not by
+    /// package, so the zone is found by its `package-N` name. reading the counter needs root on
+    /// kernels since 5.10
+    fn package_energy_uj(&self, package: u8) -> Option<u64> {
+        let name = format!("package-{package}");
+        // zones are symlinks, so they are not filtered on being directories
+        let zone = self.fs.dir(POWERCAP_DIR).ok()?.iter().find_map(|dirent| {
+            let zone = F::dirent_path(dirent).to_str()?;
+            (self.read(&format!("{zone}/name")).ok()? == name).then(|| zone.to_owned())
+        })?;
+        self.read(&format!("{zone}/energy_uj")).ok()?.parse().ok()
+    }
+
+    fn set_platform_profile(&mut self, profile: &str) -> Result<()> {
+        let choices = self.read(PLATFORM_PROFILE_CHOICES)?;
+        if !choices.split_whitespace().any(|choice| choice == profile) {
+            return Err(ServerError::InvalidPlatformProfile);
+        }
+        self.write(PLATFORM_PROFILE, profile)
+    }
+
+    /// through [SMT_CONTROL] where the kernel has it. otherwise disabling offlines every thread
+    /// sibling but the lowest numbered one of each physical core, and enabling onlines only the
+    /// cores an earlier disable offlined, leaving cores offlined for other reasons alone
+    fn set_smt(&mut self, enabled: bool) -> Result<()> {
+        if self.exists(SMT_CONTROL)? {
+            return self.write(SMT_CONTROL, if enabled { "on" } else { "off" });
+        }
+        if enabled {
+            let offlined: Vec<u8> = self.state.lock().smt_offlined.iter().copied().collect();
+            for core in offlined {
+                self.write(&online_path(core), "1")?;
+                self.state.lock().smt_offlined.remove(&core);
+            }
+            return Ok(());
+        }
+
+        let cores = self.resolve(&CpuCores::All)?;
+        let mut secondaries = BTreeSet::new();
+        for core in cores {
+            let path = format!("{CPU_DIR}/cpu{core}/topology/thread_siblings_list");
+            // offline cores have no topology
+            let Ok(siblings) = self.read(&path) else {
+                continue;
+            };
+            let siblings = parse_cpu_list(&siblings)
+                .ok_or_else(|| ServerError::Other(io::Error::from(ErrorKind::InvalidData)))?;
+            if let Some(primary) = siblings.iter().min() {
+                secondaries.extend(siblings.iter().filter(|sibling| *sibling != primary));
+            }
+        }
+        // cpu0 is always a primary, but it must never be offlined
+        for core in secondaries.into_iter().filter(|core| *core != 0) {
+            self.write(&online_path(core), "0")?;
+            self.state.lock().smt_offlined.insert(core);
+        }
+        Ok(())
+    }
+
+    /// see [Request::ResetAll]. files a driver does not provide are skipped, as are cores without
+    /// cpufreq files
+    fn reset_all(&mut self) -> Result<()> {
+        for core in self.resolve(&CpuCores::All)? {
+            if !self.core_info(core, &GetOptions::default())?.tunable() {
+                continue;
+            }
+
+            let available = self.available_governors(core)?;
+            let default = match self.read_attr(core, "scaling_driver").ok().as_deref() {
+                Some("intel_pstate" | "amd-pstate-epp") => Some("powersave"),
+                _ if available.iter().any(|governor| governor == "schedutil") => Some("schedutil"),
+                _ => available.first().map(String::as_str),
+            };
+            if let Some(default) = default {
+                self.apply(core, &ScalingType::Preset(default.into()))?;
+            }
+
+            // raise the maximum first so the minimum never ends up above it
+            for (limit, hardware) in [
+                ("scaling_max_freq", "cpuinfo_max_freq"),
+                ("scaling_min_freq", "cpuinfo_min_freq"),
+            ] {
+                if let Some(khz) = self.read_khz(core, hardware) {
+                    self.write_attr(core, limit, &khz.to_string())?;
+                }
+            }
+
+            // EPP can only be changed once the governor is no longer `performance`
+            if self.exists(&attr_path(core, "energy_performance_preference"))? {
+                self.write_attr(core, "energy_performance_preference", "default")?;
+            }
+        }
+
+        match self.set_boost(true) {
+            Err(ServerError::Unsupported) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// enable or disable turbo boost through `cpufreq/boost`, or `intel_pstate/no_turbo` which is
+    /// inverted. fails with [ServerError::Unsupported] if neither exists
+    fn set_boost(&mut self, enabled: bool) -> Result<()> {
+        let boost = format!("{CPU_DIR}/cpufreq/boost");
+        let no_turbo = format!("{CPU_DIR}/intel_pstate/no_turbo");
+        if self.exists(&boost)? {
+            self.write(&boost, if enabled { "1" } else { "0" })
+        } else if self.exists(&no_turbo)? {
+            self.write(&no_turbo, if enabled { "0" } else { "1" })
+        } else {
+            Err(ServerError::Unsupported)
+        }
+    }
+
+    /// see [Request::ListProfiles]
+    fn list_profiles(&self) -> Vec<(String, String)> {
+        let mut profiles: Vec<_> = self
+            .options
+            .profiles
+            .iter()
+            .map(|(name, profile)| (name.clone(), profile.summary()))
+            .collect();
+        profiles.sort_unstable();
+        profiles
+    }
+
+    /// see [Request::ApplyProfile]. the governor is set before EPP since EPP cannot be changed
+    /// under `performance`. cores without cpufreq files are skipped, as are cores without EPP for
+    /// the EPP
+    fn apply_profile(&mut self, name: &str) -> Result<()> {
+        let profile = self
+            .options
+            .profiles
+            .get(name)
+            .cloned()
+            .ok_or(ServerError::UnknownProfile)?;
+
+        for core in self.resolve(&CpuCores::All)? {
+            if !self.core_info(core, &GetOptions::default())?.tunable() {
+                continue;
+            }
+            if let Some(governor) = &profile.governor {
+                self.apply(core, &ScalingType::Preset(governor.clone()))?;
+            }
+            if let Some(epp) = &profile.epp
+                && self.exists(&attr_path(core, "energy_performance_preference"))?
+            {
+                self.write_attr(core, "energy_performance_preference", epp)?;
+            }
+        }
+
+        match profile.boost {
+            Some(enabled) => self.set_boost(enabled),
+            None => Ok(()),
+        }
+    }
+
+    /// see [Request::SetOnline]. a token only confirms offlining the exact cores it was issued for
+    fn set_online(
+        &mut self,
+        cores: &CpuCores,
+        online: bool,
+        confirmation: Option<u64>,
+    ) -> Result<Response<io::Error>> {
+        let cores: Vec<u8> = self
+            .resolve(cores)?
+            .into_iter()
+            .filter(|core| *core != 0)
+            .collect();
+
+        if !online && cores.len() > 1 {
+            let now = self.clock.monotonic_ms();
+            let mut state = self.state.lock();
+            state
+                .confirmations
+                .retain(|_, (_, issued)| now.saturating_sub(*issued) < CONFIRMATION_TIMEOUT_MS);
+            let confirmed = confirmation
+                .and_then(|token| state.confirmations.remove(&token))
+                .is_some_and(|(confirmed, _)| confirmed == cores);
+            if !confirmed {
+                // a random token, so that one cannot be guessed from another
+                let token = RandomState::new().build_hasher().finish();
+                state.confirmations.insert(token, (cores, now));
+                return Ok(Response::ConfirmationRequired(token));
+            }
+        }
+
+        let value = if online { "1" } else { "0" };
+        for core in cores {
+            self.write(&online_path(core), value)?;
+        }
+        Ok(Response::Ack)
+    }
+
+    fn get_stats(&self, cores: &CpuCores) -> Result<HashMap<u8, CoreStats>> {
+        let mut stats = HashMap::new();
+        for core in self.resolve(cores)? {
+            let total_trans = match self.read_attr(core, "stats/total_trans") {
+                Ok(total_trans) => total_trans
+                    .parse()
+                    .map_err(|_| ServerError::Other(io::Error::from(ErrorKind::InvalidData)))?,
+                Err(ServerError::Other(e)) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            let time_in_state = self
+                .read_attr(core, "stats/time_in_state")
+                .ok()
+                .and_then(|stats| parse_time_in_state(&stats))
+                .unwrap_or_default();
+            stats.insert(
+                core,
+                CoreStats {
+                    total_trans,
+                    time_in_state,
+                },
+            );
+        }
+        if stats.is_empty() {
+            return Err(ServerError::Unsupported);
+        }
+        Ok(stats)
+    }
+
+    fn reset_stats(&mut self, cores: &CpuCores) -> Result<()> {
+        let mut reset = false;
+        for core in self.resolve(cores)? {
+            let path = attr_path(core, "stats/reset");
+            if self.exists(&path)? {
+                self.write(&path, "1")?;
+                reset = true;
+            }
+        }
+        if !reset {
+            return Err(ServerError::Unsupported);
+        }
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Result<Capabilities> {
+        let core = self.first_cpufreq_core()?;
+        Ok(Capabilities {
+            driver: core.and_then(|core| self.read_attr(core, "scaling_driver").ok()),
+            governors: match core {
+                Some(core) => self.available_governors(core)?,
+                None => Vec::new(),
+            },
+            features: self.features().clone(),
+            boost_enabled: self.boost_enabled(),
+            competitors: if self.options.detect_competitors {
+                detect_competitors(&self.fs)
+            } else {
+                Vec::new()
+            },
+            writes_effective: self.state.lock().writes_effective,
+        })
+    }
+
+    /// the first online core with cpufreq support, which need not be cpu0. [None] if no core has
+    /// it, eg under a hypervisor that does not expose frequency scaling
+    fn first_cpufreq_core(&self) -> Result<Option<u8>> {
+        let cores = match self.online_cores()? {
+            Some(online) => online,
+            None => self.present_cores()?,
+        };
+        for core in cores {
+            if self.exists(&attr_path(core, "scaling_available_governors"))? {
+                return Ok(Some(core));
+            }
+        }
+        Ok(None)
+    }
+
+    /// `cpufreq/boost` is 1 when boost is enabled, `intel_pstate/no_turbo` is 1 when it is disabled
+    fn boost_enabled(&self) -> Option<bool> {
+        if let Ok(boost) = self.read(&format!("{CPU_DIR}/cpufreq/boost")) {
+            return Some(boost == "1");
+        }
+        let no_turbo = self
+            .read(&format!("{CPU_DIR}/intel_pstate/no_turbo"))
+            .ok()?;
+        Some(no_turbo == "0")
+    }
+
+    pub(crate) fn features(&self) -> &FeatureSet {
+        self.features.get_or_init(|| detect_features(&self.fs))
+    }
+
+    fn pstate_status(&self) -> Result<String> {
+        if !self.exists(PSTATE_STATUS)? {
+            return Err(ServerError::Unsupported);
+        }
+        self.read(PSTATE_STATUS)
+    }
+
+    fn nmi_watchdog(&self) -> Result<bool> {
+        if !self.exists(NMI_WATCHDOG)? {
+            return Err(ServerError::Unsupported);
+        }
+        Ok(self.read(NMI_WATCHDOG)? != "0")
+    }
+
+    fn set_nmi_watchdog(&mut self, enabled: bool) -> Result<()> {
+        if !self.exists(NMI_WATCHDOG)? {
+            return Err(ServerError::Unsupported);
+        }
+        self.write(NMI_WATCHDOG, if enabled { "1" } else { "0" })
+    }
+
+    fn set_pstate_status(&mut self, status: &str) -> Result<()> {
+        if !self.exists(PSTATE_STATUS)? {
+            return Err(ServerError::Unsupported);
+        }
+        if !matches!(status, "active" | "passive" | "off") {
+            return Err(ServerError::InvalidPstateStatus);
+        }
+        self.write(PSTATE_STATUS, status)
+    }
+
+    /// every attribute in [ATTRIBUTES] that can be read, unreadable ones are left out
+    fn dump_core(&self, core: u8) -> HashMap<String, String> {
+        ATTRIBUTES
+            .iter()
+            .filter_map(|attr| Some((attr.to_string(), self.read_attr(core, attr).ok()?)))
+            .collect()
+    }
+
+    fn core_info(&self, core: u8, options: &GetOptions) -> Result<CoreInfo> {
+        let governor = match self.read_attr(core, "scaling_governor") {
+            Ok(governor) => governor,
+            // either the core was hotplugged out after it was enumerated, or the kernel has no
+            // cpufreq sysfs tree and only /proc/cpuinfo, which lists online cores, knows anything
+            Err(ServerError::Other(e)) if e.kind() == ErrorKind::NotFound => {
+                return Ok(self.cpuinfo_fallback(core).unwrap_or_default());
+            }
+            Err(e) => return Err(e),
+        };
+        let frequency = if options.include_frequency {
+            self.read_frequency(core, "scaling_cur_freq")
+        } else {
+            FrequencyReading::Unavailable
+        };
+        let available_frequencies = self
+            .read_attr(core, "scaling_available_frequencies")
+            .ok()
+            .and_then(|table| {
+                table
+                    .split_whitespace()
+                    .map(|khz| khz.parse().ok())
+                    .collect::<Option<Vec<u64>>>()
+            });
+        Ok(CoreInfo {
+            governor,
+            frequency,
+            online: true,
+            scaling_min_khz: self.read_khz(core, "scaling_min_freq"),
+            scaling_max_khz: self.read_khz(core, "scaling_max_freq"),
+            cpuinfo_min_khz: self.read_khz(core, "cpuinfo_min_freq"),
+            cpuinfo_max_khz: self.read_khz(core, "cpuinfo_max_freq"),
+            thread_siblings: self
+                .read(&format!(
+                    "{CPU_DIR}/cpu{core}/topology/thread_siblings_list"
+                ))
+                .ok()
+                .and_then(|siblings| parse_cpu_list(&siblings)),
+            measured_khz: options
+                .include_measured
+                .then(|| self.read_khz(core, "cpuinfo_cur_freq"))
+                .flatten(),
+            base_frequency_khz: self.read_khz(core, "base_frequency"),
+            // a missing file means the stats module is not loaded
+            time_in_state: options
+                .include_time_in_state
+                .then(|| self.read_attr(core, "stats/time_in_state").ok())
+                .flatten()
+                .and_then(|stats| parse_time_in_state(&stats)),
+            frequency_step_khz: available_frequencies.as_deref().and_then(frequency_step),
+            available_frequencies,
+        })
+    }
+
+    fn apply(&mut self, core: u8, scaling: &ScalingType) -> Result<()> {
+        match scaling {
+            ScalingType::Preset(governor) => {
+                let governor = &normalize_governor_name(governor);
+                let available = self.available_governors(core)?;
+                if available.is_empty() {
+                    return Err(ServerError::NoGovernorsAvailable);
+                }
+                if !available.contains(governor) {
+                    return Err(ServerError::InvalidScalingGovernor);
+                }
+                self.write_attr(core, "scaling_governor", governor)?;
+                if self.options.verify_writes
+                    && self.read_expected(&attr_path(core, "scaling_governor"))? != *governor
+                {
+                    return Err(ServerError::WriteNotApplied);
+                }
+                let now = self.clock.monotonic_ms();
+                let mut state = self.state.lock();
+                state.last_change.insert(core, now);
+                state.events.publish(Event::GovernorApplied {
+                    core,
+                    governor: governor.clone(),
+                });
+                Ok(())
+            }
+            ScalingType::AdjustMax(delta) => {
+                let max = self.read_attr_khz(core, "scaling_max_freq")?;
+                let lower = self.read_khz(core, "cpuinfo_min_freq").unwrap_or(0);
+                let upper = self.read_khz(core, "cpuinfo_max_freq").unwrap_or(u64::MAX);
+                let adjusted = max.saturating_add_signed(*delta).clamp(lower, upper);
+                self.write_attr(core, "scaling_max_freq", &adjusted.to_string())
+            }
+            ScalingType::Pin => {
+                if let Ok("intel_pstate" | "amd-pstate-epp") =
+                    self.read_attr(core, "scaling_driver").as_deref()
+                {
+                    return self.apply(core, &ScalingType::Preset("performance".into()));
+                }
+                let max = self.read_attr_khz(core, "cpuinfo_max_freq")?;
+                self.set_limits(core, max, max)
+            }
+            ScalingType::WithFallback {
+                primary,
+                fallback,
+                after,
+            } => {
+                self.apply(core, &ScalingType::Preset(primary.to_string()))?;
+                let after_ms = after.as_millis() as u64;
+                let fallback = Fallback {
+                    governor: fallback.to_string(),
+                    deadline_ms: self.clock.monotonic_ms() + after_ms,
+                    after_ms,
+                };
+                self.state.lock().fallbacks.insert(core, fallback);
+                Ok(())
+            }
+            ScalingType::Park => {
+                if let Ok("intel_pstate" | "amd-pstate-epp") =
+                    self.read_attr(core, "scaling_driver").as_deref()
+                {
+                    self.apply(core, &ScalingType::Preset("powersave".into()))?;
+                }
+                let min = self.read_attr_khz(core, "cpuinfo_min_freq")?;
+                self.set_limits(core, min, min)
+            }
+            // the check does not depend on the core, so it fails on the first core before any
+            // core has been written
+            ScalingType::MinMax { min_khz, max_khz } if min_khz > max_khz => {
+                Err(ServerError::InvalidLimits)
+            }
+            ScalingType::MinMax { min_khz, max_khz } => self.set_limits(core, *min_khz, *max_khz),
+        }
+    }
+
+    /// write `scaling_min_freq` and `scaling_max_freq` in whichever order keeps the minimum from
+    /// ever going above the maximum, which the kernel would reject
+    fn set_limits(&mut self, core: u8, min_khz: u64, max_khz: u64) -> Result<()> {
+        let (min, max) = (min_khz.to_string(), max_khz.to_string());
+        if self
+            .read_khz(core, "scaling_max_freq")
+            .is_some_and(|current| min_khz > current)
+        {
+            self.write_attr(core, "scaling_max_freq", &max)?;
+            self.write_attr(core, "scaling_min_freq", &min)
+        } else {
+            self.write_attr(core, "scaling_min_freq", &min)?;
+            self.write_attr(core, "scaling_max_freq", &max)
+        }
+    }
+
+    fn available_governors(&self, core: u8) -> Result<Vec<String>> {
+        Ok(split_governors(&self.read_expected(&attr_path(
+            core,
+            "scaling_available_governors",
+        ))?))
+    }
+
+    /// every present core when given [CpuCores::All], otherwise the selected cores
+    fn resolve(&self, cores: &CpuCores) -> Result<Vec<u8>> {
+        Ok(cores.resolve(self.core_count()?))
+    }
+
+    /// the cores in the `online` cpu list, [None] if there is no such file
+    fn online_cores(&self) -> Result<Option<Vec<u8>>> {
+        let online = match self.read(&format!("{CPU_DIR}/online")) {
+            Ok(online) => online,
+            Err(ServerError::Other(e)) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        parse_cpu_list(&online)
+            .map(Some)
+            .ok_or_else(|| ServerError::Other(io::Error::from(ErrorKind::InvalidData)))
+    }
+
+    /// the cores in the `present` cpu list
+    fn present_cores(&self) -> Result<Vec<u8>> {
+        let present = self.read(&format!("{CPU_DIR}/present"))?;
+        parse_cpu_list(&present)
+            .ok_or_else(|| ServerError::Other(io::Error::from(ErrorKind::InvalidData)))
+    }
+
+    /// one more than the highest core in `present`
+    fn core_count(&self) -> Result<u8> {
+        let present = self.present_cores()?;
+        Ok(present.iter().max().map_or(0, |max| max.saturating_add(1)))
+    }
+
+    /// a frequency attribute in kHz that must be present
+    fn read_attr_khz(&self, core: u8, attr: &str) -> Result<u64> {
+        self.read_expected(&attr_path(core, attr))?
+            .parse()
+            .map_err(|_| ServerError::Other(io::Error::from(ErrorKind::InvalidData)))
+    }
+
+    /// the little [PROC_CPUINFO] knows about a core without cpufreq files: that it is online and
+    /// its approximate frequency. the governor is left empty, which marks the core as not
+    /// [tunable](CoreInfo::tunable) so that loops over every core skip it. [None] if it is not
+    /// listed
+    fn cpuinfo_fallback(&self, core: u8) -> Option<CoreInfo> {
+        let khz = *parse_cpuinfo(&self.read(PROC_CPUINFO).ok()?).get(&core)?;
+        Some(CoreInfo {
+            frequency: FrequencyReading::Approximate(khz),
+            online: true,
+            ..Default::default()
+        })
+    }
+
+    /// a frequency attribute of an online core
+    fn read_frequency(&self, core: u8, attr: &str) -> FrequencyReading {
+        match self.read_attr(core, attr) {
+            Ok(khz) => khz
+                .parse()
+                .map_or(FrequencyReading::Unparseable, FrequencyReading::Known),
+            Err(_) => FrequencyReading::Unavailable,
+        }
+    }
+
+    /// a frequency attribute in kHz, [None] if it is missing or unreadable
+    fn read_khz(&self, core: u8, attr: &str) -> Option<u64> {
+        self.read_attr(core, attr).ok()?.parse().ok()
+    }
+
+    fn read_attr(&self, core: u8, attr: &str) -> Result<String> {
+        self.read(&attr_path(core, attr))
+    }
+
+    /// also drops `core` from the shared info cache, even if the write failed part way, so that
+    /// streams see the change on their next poll. this happens after the write and bumps the
+    /// info generation, so a stream that read the core before the write finished does not cache
+    /// its stale read
+    fn write_attr(&mut self, core: u8, attr: &str, content: &str) -> Result<()> {
+        let result = self.write(&attr_path(core, attr), content);
+        self.state.lock().invalidate_info(Some(core));
+        result
+    }
+
+    /// [Server::read] a file that should exist, retrying up to [ServerOptions::read_retries] times
+    /// [READ_RETRY_DELAY] apart while it is missing. files briefly disappear while their core is
+    /// being hotplugged
+    fn read_expected(&self, path: &str) -> Result<String> {
+        let mut retries = self.options.read_retries;
+        loop {
+            match self.read(path) {
+                Err(ServerError::Other(e)) if e.kind() == ErrorKind::NotFound && retries > 0 => {
+                    retries -= 1;
+                    self.clock.sleep(READ_RETRY_DELAY);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn exists(&self, path: &str) -> Result<bool> {
+        self.fs.exists(path).map_err(fs_error)
+    }
+
+    pub(crate) fn read(&self, path: &str) -> Result<String> {
+        let mut file = self
+            .fs
+            .open(OpenOptions::default().read(true), path)
+            .map_err(fs_error)?;
+        let content = self.fs.read_to_string(&mut file).map_err(fs_error)?;
+        Ok(content.trim().into())
+    }
+
+    pub(crate) fn write(&mut self, path: &str, content: &str) -> Result<()> {
+        let mut file = self
+            .fs
+            .open(OpenOptions::default().write(true), path)
+            .map_err(fs_error)?;
+        self.fs
+            .write_to(&mut file, content.as_bytes())
+            .map_err(fs_error)
+    }
+}
+
+/// an [Fs] error as a [ServerError]. [ErrorKind::Unsupported] means the [Fs] has no cpufreq
+/// support at all, as with [StubFs](crate::fs::StubFs)
+fn fs_error(e: io::Error) -> ServerError<io::Error> {
+    match e.kind() {
+        ErrorKind::Unsupported => ServerError::Unsupported,
+        _ => ServerError::Other(e),
+    }
+}
+
+/// the governors listed in a `scaling_available_governors` file
+fn split_governors(available: &str) -> Vec<String> {
+    available.split_whitespace().map(String::from).collect()
+}
+
+/// path of the file that onlines and offlines `core`
+pub(crate) fn online_path(core: u8) -> String {
+    format!("{CPU_DIR}/cpu{core}/online")
+}
+
+/// path of a cpufreq attribute of `core`, eg `/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor`
+pub(crate) fn attr_path(core: u8, attr: &str) -> String {
+    format!("{CPU_DIR}/cpu{core}/cpufreq/{attr}")
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,2086 @@
+use std::cell::{Cell, RefCell};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use libcpufreq::Governor;
+
+use super::*;
+use crate::fs::{StubFs, TestFs};
+
+/// what [MockClock]'s wall clock reads at monotonic time 0
+const WALL_CLOCK_START_MS: u64 = 1_700_000_000_000;
+
+/// a [Clock] that only moves when slept on, shared by every clone
+#[derive(Debug, Clone, Default)]
+struct MockClock(Rc<Cell<u64>>);
+
+impl Clock for MockClock {
+    fn now_ms(&self) -> u64 {
+        WALL_CLOCK_START_MS + self.0.get()
+    }
+
+    fn monotonic_ms(&self) -> u64 {
+        self.0.get()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.0.set(self.0.get() + duration.as_millis() as u64);
+    }
+}
+
+/// a [TestFs] holding `files`, given as `(path, content)`
+fn fixture(files: &[(&str, &str)]) -> TestFs {
+    TestFs(
+        files
+            .iter()
+            .map(|(path, content)| (Arc::from(Path::new(path)), content.to_string()))
+            .collect(),
+    )
+}
+
+/// three present cores: cpu0 and cpu2 on `powersave` and cpu1 on `performance`, with cpu0 at
+/// 2.4 GHz
+fn three_cores() -> Vec<(&'static str, &'static str)> {
+    vec![
+        ("/sys/devices/system/cpu/present", "0-2\n"),
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+            "powersave\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu1/cpufreq/scaling_governor",
+            "performance\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu2/cpufreq/scaling_governor",
+            "powersave\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_available_governors",
+            "performance powersave\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu1/cpufreq/scaling_available_governors",
+            "performance powersave\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu2/cpufreq/scaling_available_governors",
+            "performance powersave\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_cur_freq",
+            "2400000\n",
+        ),
+    ]
+}
+
+/// [three_cores] with cpu0's scaling limits at 800 MHz to 3 GHz, hardware limits of 400 MHz to
+/// 3.5 GHz and `driver` as its cpufreq driver
+fn limited_cores(driver: &'static str) -> Vec<(&'static str, &'static str)> {
+    let mut files = three_cores();
+    files.extend([
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_driver",
+            driver,
+        ),
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_min_freq",
+            "800000\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_max_freq",
+            "3000000\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/cpuinfo_min_freq",
+            "400000\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/cpuinfo_max_freq",
+            "3500000\n",
+        ),
+    ]);
+    files
+}
+
+/// cpu0's `(governor, scaling_min_freq, scaling_max_freq)`
+fn cpu0_scaling<C: Clock>(server: &Server<TestFs, C>) -> (String, String, String) {
+    let attr = |attr| file(server, &attr_path(0, attr)).unwrap();
+    (
+        attr("scaling_governor"),
+        attr("scaling_min_freq"),
+        attr("scaling_max_freq"),
+    )
+}
+
+/// runs before a file is opened, with the files and the path being opened
+type OpenHook = Box<dyn Fn(&mut TestFs, &str)>;
+
+/// a [TestFs] with hooks to act like a live sysfs, whose files change and disappear under the
+/// server. clones share the files, like the sysfs every connection of a daemon sees
+#[derive(Clone)]
+struct HookedFs {
+    fs: Rc<RefCell<TestFs>>,
+    hooks: Rc<Vec<OpenHook>>,
+    /// accept every write and keep the old content, like firmware that locks cpufreq
+    ignore_writes: bool,
+    /// the path of every file opened, to count reads
+    opened: Rc<RefCell<Vec<String>>>,
+}
+
+impl HookedFs {
+    fn new(fs: TestFs) -> Self {
+        Self {
+            fs: Rc::new(RefCell::new(fs)),
+            hooks: Rc::default(),
+            ignore_writes: false,
+            opened: Rc::default(),
+        }
+    }
+
+    /// run `hook` before every open
+    fn on_open(mut self, hook: impl Fn(&mut TestFs, &str) + 'static) -> Self {
+        Rc::get_mut(&mut self.hooks)
+            .expect("hooks are added before the fs is cloned")
+            .push(Box::new(hook));
+        self
+    }
+
+    /// everything below `dir` disappears the first time a file there is opened, like a core
+    /// hotplugged out while its information is being gathered
+    fn vanishing(self, dir: &'static str) -> Self {
+        self.on_open(move |fs, path| {
+            if Path::new(path).starts_with(dir) {
+                fs.0.retain(|file, _| !file.starts_with(dir));
+            }
+        })
+    }
+
+    /// each open of a scripted path first takes the next step of its script: [Some] replaces the
+    /// file's content, [None] removes the file. once a script runs out the file is left as is
+    fn scripted(self, scripts: &[(&str, &[Option<&'static str>])]) -> Self {
+        let scripts: HashMap<String, Vec<Option<&'static str>>> = scripts
+            .iter()
+            .map(|(path, script)| (path.to_string(), script.iter().rev().copied().collect()))
+            .collect();
+        let scripts = RefCell::new(scripts);
+        self.on_open(move |fs, path| {
+            let step = scripts.borrow_mut().get_mut(path).and_then(Vec::pop);
+            match step {
+                Some(Some(content)) => {
+                    fs.0.insert(Arc::from(Path::new(path)), content.into());
+                }
+                Some(None) => {
+                    fs.0.remove(Path::new(path));
+                }
+                None => {}
+            }
+        })
+    }
+
+    fn ignoring_writes(self) -> Self {
+        Self {
+            ignore_writes: true,
+            ..self
+        }
+    }
+
+    /// how many times a file whose path ends with `suffix` was opened
+    fn opens(&self, suffix: &str) -> usize {
+        let opened = self.opened.borrow();
+        opened.iter().filter(|path| path.ends_with(suffix)).count()
+    }
+}
+
+impl Fs for HookedFs {
+    type File = <TestFs as Fs>::File;
+    type DirEnt = <TestFs as Fs>::DirEnt;
+
+    fn exists(&self, path: &str) -> io::Result<bool> {
+        self.fs.borrow().exists(path)
+    }
+
+    fn open(&self, options: OpenOptions, path: &str) -> io::Result<Self::File> {
+        self.opened.borrow_mut().push(path.to_string());
+        for hook in self.hooks.iter() {
+            hook(&mut self.fs.borrow_mut(), path);
+        }
+        self.fs.borrow().open(options, path)
+    }
+
+    fn read_to_string(&self, f: &mut Self::File) -> io::Result<String> {
+        self.fs.borrow().read_to_string(f)
+    }
+
+    fn write_to(&mut self, f: &mut Self::File, content: &[u8]) -> io::Result<()> {
+        if self.ignore_writes {
+            return Ok(());
+        }
+        self.fs.borrow_mut().write_to(f, content)
+    }
+
+    fn dir(&self, path: &str) -> io::Result<Vec<Self::DirEnt>> {
+        self.fs.borrow().dir(path)
+    }
+
+    fn is_dir(dirent: &Self::DirEnt) -> bool {
+        TestFs::is_dir(dirent)
+    }
+
+    fn dirent_path(dirent: &Self::DirEnt) -> &Path {
+        TestFs::dirent_path(dirent)
+    }
+}
+
+/// `(core, governor, online)` of every core in `response`, sorted by core
+fn governors(response: Response<io::Error>) -> Vec<(u8, String, bool)> {
+    let Response::Information(info) = response else {
+        panic!("not information");
+    };
+    info.by_core()
+        .into_iter()
+        .map(|(core, info)| (core, info.governor.clone(), info.online))
+        .collect()
+}
+
+/// the content of `path` in the server's [TestFs], trimmed as [Server::read] does
+fn file<C: Clock>(server: &Server<TestFs, C>, path: &str) -> Option<String> {
+    Some(server.fs.0.get(Path::new(path))?.trim().to_string())
+}
+
+#[test]
+fn subscribe_frames_are_timestamped_an_interval_apart() {
+    let clock = MockClock::default();
+    let mut server = Server::with_clock(fixture(&three_cores()), ServerOptions::default(), clock);
+    let request = Request::Subscribe(vec![(CpuCores::One(0), Duration::from_secs(1))]);
+    let mut timestamps = Vec::new();
+    server.serve(request, |response| {
+        if let Response::Frame { timestamp_ms, .. } = response {
+            timestamps.push(timestamp_ms);
+        }
+        timestamps.len() < 3
+    });
+    let start = WALL_CLOCK_START_MS;
+    assert_eq!(timestamps, [start, start + 1000, start + 2000]);
+}
+
+#[test]
+fn set_where_only_changes_matching_cores() {
+    let mut server = Server::new(fixture(&three_cores()));
+    let request = Request::SetWhere {
+        current_governor: "powersave".into(),
+        new: ScalingType::Preset("performance".into()),
+    };
+    assert!(matches!(server.handle(request), Response::Ack));
+    for core in 0..3 {
+        let governor = file(&server, &attr_path(core, "scaling_governor"));
+        assert_eq!(governor.as_deref(), Some("performance"));
+    }
+    let mut changed: Vec<u8> = server.state.lock().last_change.keys().copied().collect();
+    changed.sort_unstable();
+    assert_eq!(changed, [0, 2]);
+}
+
+#[test]
+fn core_vanishing_mid_gather_is_reported_offline() {
+    let fs = HookedFs::new(fixture(&three_cores())).vanishing("/sys/devices/system/cpu/cpu2");
+    let mut server = Server::new(fs);
+    assert_eq!(
+        governors(server.handle(Request::Get(CpuCores::All))),
+        [
+            (0, "powersave".into(), true),
+            (1, "performance".into(), true),
+            (2, String::new(), false),
+        ]
+    );
+}
+
+#[test]
+fn verification_catches_an_ignored_write() {
+    let mut server = Server::new(HookedFs::new(fixture(&three_cores())).ignoring_writes());
+    let request = Request::Set(CpuCores::One(0), ScalingType::Preset("performance".into()));
+    assert!(matches!(
+        server.handle(request),
+        Response::Error(ServerError::WriteNotApplied)
+    ));
+}
+
+#[test]
+fn get_packages_groups_cores_by_package() {
+    let mut files = three_cores();
+    files.extend([
+        (
+            "/sys/devices/system/cpu/cpu0/topology/physical_package_id",
+            "0\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu1/topology/physical_package_id",
+            "1\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu2/topology/physical_package_id",
+            "0\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu2/cpufreq/scaling_cur_freq",
+            "1200000\n",
+        ),
+    ]);
+    let mut server = Server::new(fixture(&files));
+    let Response::Packages(packages) = server.handle(Request::GetPackages) else {
+        panic!("not packages");
+    };
+    assert_eq!(packages.len(), 2);
+    assert_eq!(packages[&0].governors, ["powersave"]);
+    assert_eq!(packages[&0].average_mhz, Some(1800.0));
+    assert_eq!(packages[&1].governors, ["performance"]);
+    assert_eq!(packages[&1].average_mhz, None);
+}
+
+#[test]
+fn platform_profile_read_and_set() {
+    let mut server = Server::new(fixture(&[
+        ("/sys/firmware/acpi/platform_profile", "balanced\n"),
+        (
+            "/sys/firmware/acpi/platform_profile_choices",
+            "low-power balanced performance\n",
+        ),
+    ]));
+    assert!(matches!(
+        server.handle(Request::GetPlatformProfile),
+        Response::PlatformProfile(profile) if profile == "balanced"
+    ));
+
+    let request = Request::SetPlatformProfile("performance".into());
+    assert!(matches!(server.handle(request), Response::Ack));
+    assert_eq!(
+        file(&server, PLATFORM_PROFILE).as_deref(),
+        Some("performance")
+    );
+
+    let request = Request::SetPlatformProfile("turbo".into());
+    assert!(matches!(
+        server.handle(request),
+        Response::Error(ServerError::InvalidPlatformProfile)
+    ));
+    assert_eq!(
+        file(&server, PLATFORM_PROFILE).as_deref(),
+        Some("performance")
+    );
+}
+
+#[test]
+fn dump_core_contains_the_seeded_attributes() {
+    let mut files = three_cores();
+    files.extend([
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_min_freq",
+            "800000\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_max_freq",
+            "3600000\n",
+        ),
+    ]);
+    let mut server = Server::new(fixture(&files));
+    let Response::Dump(dump) = server.handle(Request::DumpCore(0)) else {
+        panic!("not a dump");
+    };
+    assert_eq!(dump["scaling_governor"], "powersave");
+    assert_eq!(dump["scaling_min_freq"], "800000");
+    assert_eq!(dump["scaling_max_freq"], "3600000");
+    assert_eq!(dump["scaling_cur_freq"], "2400000");
+    assert!(!dump.contains_key("bios_limit"));
+}
+
+#[test]
+fn too_soon_change_is_deferred() {
+    let clock = MockClock::default();
+    let options = ServerOptions::default().min_change_interval(Some(Duration::from_secs(1)));
+    let mut server = Server::with_clock(fixture(&three_cores()), options, clock.clone());
+    let set = |governor: &str| Request::Set(CpuCores::One(0), ScalingType::Preset(governor.into()));
+    assert!(matches!(
+        server.handle(set("performance")),
+        Response::Applied { .. }
+    ));
+
+    clock.sleep(Duration::from_millis(400));
+    assert!(matches!(
+        server.handle(set("powersave")),
+        Response::Deferred {
+            retry_after_ms: 600
+        }
+    ));
+    let governor = file(&server, &attr_path(0, "scaling_governor"));
+    assert_eq!(governor.as_deref(), Some("performance"));
+
+    clock.sleep(Duration::from_millis(600));
+    assert!(matches!(
+        server.handle(set("powersave")),
+        Response::Applied { .. }
+    ));
+}
+
+#[test]
+fn pstate_status_read_and_set() {
+    let mut server = Server::new(fixture(&[(PSTATE_STATUS, "active\n")]));
+    assert!(matches!(
+        server.handle(Request::GetPstateStatus),
+        Response::PstateStatus(status) if status == "active"
+    ));
+
+    let request = Request::SetPstateStatus("passive".into());
+    assert!(matches!(server.handle(request), Response::Ack));
+    assert_eq!(file(&server, PSTATE_STATUS).as_deref(), Some("passive"));
+
+    let request = Request::SetPstateStatus("guided".into());
+    assert!(matches!(
+        server.handle(request),
+        Response::Error(ServerError::InvalidPstateStatus)
+    ));
+    assert_eq!(file(&server, PSTATE_STATUS).as_deref(), Some("passive"));
+
+    let mut server = Server::new(fixture(&three_cores()));
+    assert!(matches!(
+        server.handle(Request::GetPstateStatus),
+        Response::Error(ServerError::Unsupported)
+    ));
+}
+
+#[test]
+fn frequency_is_reported_in_megahertz_and_exact_khz() {
+    let mut files = three_cores();
+    files.push((
+        "/sys/devices/system/cpu/cpu1/cpufreq/scaling_cur_freq",
+        "2899999\n",
+    ));
+    let mut server = Server::new(fixture(&files));
+    let Response::Information(info) = server.handle(Request::Get(CpuCores::One(1))) else {
+        panic!("not information");
+    };
+    let cores = info.by_core();
+    let (_, info) = cores.iter().find(|(core, _)| *core == 1).unwrap();
+    assert_eq!(info.megahertz, Some(2899));
+    assert_eq!(info.khz, Some(2_899_999));
+}
+
+#[test]
+fn adjust_max_is_clamped_to_the_hardware_limits() {
+    let mut files = three_cores();
+    files.extend([
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_max_freq",
+            "3000000\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/cpuinfo_min_freq",
+            "800000\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/cpuinfo_max_freq",
+            "3500000\n",
+        ),
+    ]);
+    let mut server = Server::new(fixture(&files));
+    let max = attr_path(0, "scaling_max_freq");
+    let adjust = |delta| Request::Set(CpuCores::One(0), ScalingType::AdjustMax(delta));
+    assert!(matches!(
+        server.handle(adjust(1_000_000)),
+        Response::Applied { .. }
+    ));
+    assert_eq!(file(&server, &max).as_deref(), Some("3500000"));
+    assert!(matches!(
+        server.handle(adjust(-5_000_000)),
+        Response::Applied { .. }
+    ));
+    assert_eq!(file(&server, &max).as_deref(), Some("800000"));
+}
+
+#[test]
+fn core_count_of_four_present_cores() {
+    let mut server = Server::new(fixture(&[
+        ("/sys/devices/system/cpu/present", "0-3\n"),
+        ("/sys/devices/system/cpu/online", "0-1\n"),
+    ]));
+    assert!(matches!(
+        server.handle(Request::CoreCount),
+        Response::CoreCount(4)
+    ));
+}
+
+#[test]
+fn boot_governor_switches_to_runtime_after_the_delay() {
+    let clock = MockClock::default();
+    let options = ServerOptions::default().boot_governors(Some(BootGovernors {
+        boot: "performance".into(),
+        runtime: "powersave".into(),
+        delay: Duration::from_secs(30),
+    }));
+    let mut server = Server::with_clock(fixture(&three_cores()), options, clock.clone());
+    let events = server.state().lock().events.subscribe();
+    server.apply_boot_governors().unwrap();
+
+    let applied: Vec<Event> = events.try_iter().collect();
+    let expected: Vec<Event> = ["performance", "powersave"]
+        .into_iter()
+        .flat_map(|governor| {
+            (0..3).map(|core| Event::GovernorApplied {
+                core,
+                governor: governor.into(),
+            })
+        })
+        .collect();
+    assert_eq!(applied, expected);
+    assert_eq!(clock.monotonic_ms(), 30_000);
+    let state = server.state().lock();
+    assert!((0..3).all(|core| state.last_change[&core] == 30_000));
+}
+
+#[test]
+fn set_smt_uses_the_control_file_where_there_is_one() {
+    let mut server = Server::new(fixture(&[
+        (SMT_CONTROL, "on\n"),
+        ("/sys/devices/system/cpu/present", "0-1\n"),
+        ("/sys/devices/system/cpu/cpu1/online", "1\n"),
+        (
+            "/sys/devices/system/cpu/cpu1/topology/thread_siblings_list",
+            "0-1\n",
+        ),
+    ]));
+    assert!(matches!(
+        server.handle(Request::SetSmt(false)),
+        Response::Ack
+    ));
+    assert_eq!(file(&server, SMT_CONTROL).as_deref(), Some("off"));
+    assert_eq!(file(&server, &online_path(1)).as_deref(), Some("1"));
+    assert!(matches!(
+        server.handle(Request::SetSmt(true)),
+        Response::Ack
+    ));
+    assert_eq!(file(&server, SMT_CONTROL).as_deref(), Some("on"));
+}
+
+#[test]
+fn set_smt_onlines_only_the_siblings_it_offlined() {
+    let mut server = Server::new(fixture(&[
+        ("/sys/devices/system/cpu/present", "0-4\n"),
+        ("/sys/devices/system/cpu/cpu1/online", "1\n"),
+        ("/sys/devices/system/cpu/cpu2/online", "1\n"),
+        ("/sys/devices/system/cpu/cpu3/online", "1\n"),
+        // offlined by someone else, so it has no topology
+        ("/sys/devices/system/cpu/cpu4/online", "0\n"),
+        (
+            "/sys/devices/system/cpu/cpu0/topology/thread_siblings_list",
+            "0,2\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu1/topology/thread_siblings_list",
+            "1,3\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu2/topology/thread_siblings_list",
+            "0,2\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu3/topology/thread_siblings_list",
+            "1,3\n",
+        ),
+    ]));
+    let online = |server: &Server<TestFs, SystemClock>| -> Vec<String> {
+        (1..=4)
+            .map(|core| file(server, &online_path(core)).unwrap())
+            .collect()
+    };
+    assert!(matches!(
+        server.handle(Request::SetSmt(false)),
+        Response::Ack
+    ));
+    assert_eq!(online(&server), ["1", "0", "0", "0"]);
+    assert!(matches!(
+        server.handle(Request::SetSmt(true)),
+        Response::Ack
+    ));
+    assert_eq!(online(&server), ["1", "1", "1", "0"]);
+}
+
+#[test]
+fn measured_frequency_is_reported_alongside_the_scaling_one() {
+    let mut files = three_cores();
+    files.push((
+        "/sys/devices/system/cpu/cpu0/cpufreq/cpuinfo_cur_freq",
+        "1800000\n",
+    ));
+    let mut server = Server::new(fixture(&files));
+    let options = GetOptions {
+        include_measured: true,
+        ..Default::default()
+    };
+    let request = Request::GetWithOptions(CpuCores::One(0), options);
+    let Response::Information(info) = server.handle(request) else {
+        panic!("not information");
+    };
+    let cores = info.by_core();
+    assert_eq!(cores[0].1.khz, Some(2_400_000));
+    assert_eq!(cores[0].1.measured_khz, Some(1_800_000));
+
+    let Response::Information(info) = server.handle(Request::Get(CpuCores::One(0))) else {
+        panic!("not information");
+    };
+    assert_eq!(info.by_core()[0].1.measured_khz, None);
+}
+
+#[test]
+fn reset_all_restores_boost_and_epp() {
+    let mut files = three_cores();
+    files.extend([
+        ("/sys/devices/system/cpu/intel_pstate/no_turbo", "1\n"),
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_driver",
+            "intel_pstate\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference",
+            "power\n",
+        ),
+    ]);
+    let mut server = Server::new(fixture(&files));
+    assert!(matches!(server.handle(Request::ResetAll), Response::Ack));
+    let no_turbo = file(&server, "/sys/devices/system/cpu/intel_pstate/no_turbo");
+    assert_eq!(no_turbo.as_deref(), Some("0"));
+    let epp = file(&server, &attr_path(0, "energy_performance_preference"));
+    assert_eq!(epp.as_deref(), Some("default"));
+    let governor = file(&server, &attr_path(0, "scaling_governor"));
+    assert_eq!(governor.as_deref(), Some("powersave"));
+}
+
+#[test]
+fn set_is_appended_to_the_request_log() {
+    let log = "/var/log/cpufreqd.log";
+    let options = ServerOptions::default().request_log(Some(log.into()));
+    let mut server = Server::with_clock(fixture(&three_cores()), options, MockClock::default())
+        .with_client("uid 1000".into());
+    let request = Request::Set(CpuCores::One(0), ScalingType::Preset("performance".into()));
+    assert!(matches!(server.handle(request), Response::Applied { .. }));
+    assert!(matches!(
+        server.handle(Request::Get(CpuCores::All)),
+        Response::Information(_)
+    ));
+    assert_eq!(
+        server.fs.0[Path::new(log)],
+        format!("{WALL_CLOCK_START_MS} uid 1000 Set(One(0), Preset(\"performance\"))\n")
+    );
+}
+
+#[test]
+fn non_root_can_read_but_not_set() {
+    let mut files = three_cores();
+    files.push((
+        PROC_STATUS,
+        "Name:\tcpufreqd\nUid:\t1000\t1000\t1000\t1000\n",
+    ));
+    let mut server = Server::new(fixture(&files));
+    assert!(matches!(
+        server.handle(Request::Get(CpuCores::All)),
+        Response::Information(_)
+    ));
+    assert!(matches!(
+        server.handle(Request::List(CpuCores::All)),
+        Response::ScalingGovernors(_)
+    ));
+    let request = Request::Set(CpuCores::One(0), ScalingType::Preset("performance".into()));
+    assert!(matches!(
+        server.handle(request),
+        Response::Error(ServerError::NotRoot)
+    ));
+    let governor = file(&server, &attr_path(0, "scaling_governor"));
+    assert_eq!(governor.as_deref(), Some("powersave"));
+}
+
+#[test]
+fn available_governors_are_read_once_per_policy() {
+    let mut server = Server::new(HookedFs::new(fixture(&[
+        ("/sys/devices/system/cpu/present", "0-3\n"),
+        (
+            "/sys/devices/system/cpu/cpufreq/policy0/related_cpus",
+            "0-3\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpufreq/policy0/scaling_available_governors",
+            "performance schedutil\n",
+        ),
+    ])));
+    let Response::ScalingGovernors(governors) = server.handle(Request::List(CpuCores::All)) else {
+        panic!("not governors");
+    };
+    assert_eq!(governors, ["performance", "schedutil"]);
+    assert_eq!(server.fs.opens("scaling_available_governors"), 1);
+}
+
+#[test]
+fn get_above_returns_only_faster_cores() {
+    let mut files = three_cores();
+    files.extend([
+        (
+            "/sys/devices/system/cpu/cpu1/cpufreq/scaling_cur_freq",
+            "4200000\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu2/cpufreq/scaling_cur_freq",
+            "2000000\n",
+        ),
+    ]);
+    let mut server = Server::new(fixture(&files));
+    assert_eq!(
+        governors(server.handle(Request::GetAbove(2_000_000))),
+        [
+            (0, "powersave".into(), true),
+            (1, "performance".into(), true)
+        ]
+    );
+    assert_eq!(
+        governors(server.handle(Request::GetAbove(4_000_000))),
+        [(1, "performance".into(), true)]
+    );
+}
+
+#[test]
+fn power_is_averaged_over_the_sample_window() {
+    let mut files = three_cores();
+    files.extend([
+        (
+            "/sys/devices/system/cpu/cpu0/topology/physical_package_id",
+            "0\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu1/topology/physical_package_id",
+            "0\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu2/topology/physical_package_id",
+            "1\n",
+        ),
+        // zone numbers need not match package numbers, and subzones are listed alongside
+        ("/sys/class/powercap/intel-rapl:0/name", "package-1\n"),
+        ("/sys/class/powercap/intel-rapl:0:0/name", "core\n"),
+        ("/sys/class/powercap/intel-rapl:1/name", "package-0\n"),
+    ]);
+    let fs = HookedFs::new(fixture(&files)).scripted(&[
+        (
+            "/sys/class/powercap/intel-rapl:0/energy_uj",
+            &[Some("500000\n"), Some("700000\n")],
+        ),
+        (
+            "/sys/class/powercap/intel-rapl:1/energy_uj",
+            &[Some("10000000\n"), Some("11500000\n")],
+        ),
+    ]);
+    let clock = MockClock::default();
+    let mut server = Server::with_clock(fs, ServerOptions::default(), clock.clone());
+    let Response::Power(power) = server.handle(Request::GetPower) else {
+        panic!("not power");
+    };
+    // 1.5 J and 0.2 J over the window
+    assert_eq!(power, HashMap::from([(0, 15.0), (1, 2.0)]));
+    assert_eq!(clock.monotonic_ms(), POWER_SAMPLE_WINDOW.as_millis() as u64);
+}
+
+#[test]
+fn read_is_retried_while_a_file_is_missing() {
+    let available = "/sys/devices/system/cpu/cpu1/cpufreq/scaling_available_governors";
+    let script: &[Option<&str>] = &[None, Some("performance powersave\n")];
+    let list = || Request::List(CpuCores::One(1));
+
+    let fs = HookedFs::new(fixture(&three_cores())).scripted(&[(available, script)]);
+    let clock = MockClock::default();
+    let options = ServerOptions::default().read_retries(2);
+    let mut server = Server::with_clock(fs, options, clock.clone());
+    assert!(matches!(
+        server.handle(list()),
+        Response::ScalingGovernors(governors) if governors == ["performance", "powersave"]
+    ));
+    assert_eq!(clock.monotonic_ms(), READ_RETRY_DELAY.as_millis() as u64);
+
+    let fs = HookedFs::new(fixture(&three_cores())).scripted(&[(available, script)]);
+    let mut server = Server::with_clock(fs, ServerOptions::default(), MockClock::default());
+    assert!(matches!(
+        server.handle(list()),
+        Response::Error(ServerError::Other(e)) if e.kind() == ErrorKind::NotFound
+    ));
+}
+
+#[test]
+fn offlining_several_cores_needs_a_confirmation() {
+    let mut files = three_cores();
+    files.extend([
+        ("/sys/devices/system/cpu/cpu1/online", "1\n"),
+        ("/sys/devices/system/cpu/cpu2/online", "1\n"),
+    ]);
+    let mut server = Server::new(fixture(&files));
+    let offline = |confirmation| Request::SetOnline {
+        cores: CpuCores::Range(1, 2),
+        online: false,
+        confirmation,
+    };
+    let Response::ConfirmationRequired(token) = server.handle(offline(None)) else {
+        panic!("no confirmation asked for");
+    };
+    assert_eq!(file(&server, &online_path(1)).as_deref(), Some("1"));
+
+    assert!(matches!(
+        server.handle(offline(Some(token.wrapping_add(1)))),
+        Response::ConfirmationRequired(_)
+    ));
+    assert!(matches!(server.handle(offline(Some(token))), Response::Ack));
+    assert_eq!(file(&server, &online_path(1)).as_deref(), Some("0"));
+    assert_eq!(file(&server, &online_path(2)).as_deref(), Some("0"));
+
+    // tokens are single use
+    assert!(matches!(
+        server.handle(offline(Some(token))),
+        Response::ConfirmationRequired(_)
+    ));
+}
+
+#[test]
+fn pin_depends_on_the_driver() {
+    let pin = || Request::Set(CpuCores::One(0), ScalingType::Pin);
+
+    let mut server = Server::new(fixture(&limited_cores("intel_pstate\n")));
+    assert!(matches!(server.handle(pin()), Response::Applied { .. }));
+    assert_eq!(
+        cpu0_scaling(&server),
+        ("performance".into(), "800000".into(), "3000000".into())
+    );
+
+    let mut server = Server::new(fixture(&limited_cores("acpi-cpufreq\n")));
+    assert!(matches!(server.handle(pin()), Response::Applied { .. }));
+    assert_eq!(
+        cpu0_scaling(&server),
+        ("powersave".into(), "3500000".into(), "3500000".into())
+    );
+}
+
+#[test]
+fn park_lowers_both_limits_to_the_hardware_minimum() {
+    let park = || Request::Set(CpuCores::One(0), ScalingType::Park);
+
+    let mut server = Server::new(fixture(&limited_cores("acpi-cpufreq\n")));
+    server.fs.0.insert(
+        Arc::from(Path::new(&attr_path(0, "scaling_governor"))),
+        "performance\n".into(),
+    );
+    assert!(matches!(server.handle(park()), Response::Applied { .. }));
+    assert_eq!(
+        cpu0_scaling(&server),
+        ("performance".into(), "400000".into(), "400000".into())
+    );
+
+    let mut server = Server::new(fixture(&limited_cores("amd-pstate-epp\n")));
+    assert!(matches!(server.handle(park()), Response::Applied { .. }));
+    assert_eq!(
+        cpu0_scaling(&server),
+        ("powersave".into(), "400000".into(), "400000".into())
+    );
+}
+
+#[test]
+fn get_stats_reports_total_transitions() {
+    let mut files = three_cores();
+    files.extend([
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/stats/total_trans",
+            "1234\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/stats/time_in_state",
+            "2400000 300\n800000 700\n",
+        ),
+    ]);
+    let mut server = Server::new(fixture(&files));
+    let Response::Stats(stats) = server.handle(Request::GetStats(CpuCores::All)) else {
+        panic!("not stats");
+    };
+    // cores without a stats directory are left out
+    assert_eq!(
+        stats,
+        HashMap::from([(
+            0,
+            CoreStats {
+                total_trans: 1234,
+                time_in_state: vec![(2_400_000, 300), (800_000, 700)],
+            }
+        )])
+    );
+}
+
+#[test]
+fn reset_stats_writes_the_reset_file() {
+    let reset = "/sys/devices/system/cpu/cpu0/cpufreq/stats/reset";
+    let mut files = three_cores();
+    files.push((reset, "\n"));
+    let mut server = Server::new(fixture(&files));
+    let request = Request::ResetStats(CpuCores::All);
+    assert!(matches!(server.handle(request), Response::Ack));
+    assert_eq!(file(&server, reset).as_deref(), Some("1"));
+
+    let mut server = Server::new(fixture(&three_cores()));
+    let request = Request::ResetStats(CpuCores::All);
+    assert!(matches!(
+        server.handle(request),
+        Response::Error(ServerError::Unsupported)
+    ));
+}
+
+#[test]
+fn fallback_applies_without_a_keepalive() {
+    let clock = MockClock::default();
+    let mut server = Server::with_clock(
+        fixture(&three_cores()),
+        ServerOptions::default(),
+        clock.clone(),
+    );
+    let request = Request::Set(
+        CpuCores::One(0),
+        ScalingType::WithFallback {
+            primary: Governor::Performance,
+            fallback: Governor::Powersave,
+            after: Duration::from_secs(10),
+        },
+    );
+    assert!(matches!(server.handle(request), Response::Applied { .. }));
+    let governor = |server: &Server<TestFs, MockClock>| {
+        file(server, &attr_path(0, "scaling_governor")).unwrap()
+    };
+
+    clock.sleep(Duration::from_secs(5));
+    assert!(matches!(server.handle(Request::Keepalive), Response::Ack));
+    clock.sleep(Duration::from_secs(7));
+    server.handle(Request::CoreCount);
+    assert_eq!(governor(&server), "performance");
+
+    clock.sleep(Duration::from_secs(3));
+    server.handle(Request::CoreCount);
+    assert_eq!(governor(&server), "powersave");
+}
+
+#[test]
+fn capabilities_report_the_boost_state_of_both_backends() {
+    let boost_enabled = |boost: Option<(&'static str, &'static str)>| {
+        let mut files = three_cores();
+        files.extend(boost);
+        let mut server = Server::new(fixture(&files));
+        let Response::Capabilities(caps) = server.handle(Request::Capabilities) else {
+            panic!("not capabilities");
+        };
+        caps.boost_enabled
+    };
+    let boost = "/sys/devices/system/cpu/cpufreq/boost";
+    let no_turbo = "/sys/devices/system/cpu/intel_pstate/no_turbo";
+    assert_eq!(boost_enabled(Some((boost, "1\n"))), Some(true));
+    assert_eq!(boost_enabled(Some((boost, "0\n"))), Some(false));
+    assert_eq!(boost_enabled(Some((no_turbo, "1\n"))), Some(false));
+    assert_eq!(boost_enabled(Some((no_turbo, "0\n"))), Some(true));
+    assert_eq!(boost_enabled(None), None);
+}
+
+#[test]
+fn stub_fs_answers_unsupported() {
+    let mut server = Server::new(StubFs);
+    let request = Request::Set(CpuCores::One(0), ScalingType::Preset("performance".into()));
+    assert!(matches!(
+        server.handle(request),
+        Response::Error(ServerError::Unsupported)
+    ));
+    assert!(matches!(
+        server.handle(Request::Get(CpuCores::All)),
+        Response::Error(ServerError::Unsupported)
+    ));
+}
+
+#[test]
+fn subscribe_tags_each_selection_at_its_own_interval() {
+    let clock = MockClock::default();
+    let mut server = Server::with_clock(fixture(&three_cores()), ServerOptions::default(), clock);
+    let request = Request::Subscribe(vec![
+        (CpuCores::One(0), Duration::from_secs(1)),
+        (CpuCores::One(1), Duration::from_secs(3)),
+    ]);
+    let mut frames = Vec::new();
+    server.serve(request, |response| {
+        if let Response::Frame {
+            timestamp_ms,
+            frame,
+            ..
+        } = response
+        {
+            let Response::Selection { id, information } = *frame else {
+                panic!("a subscription frame that is not a selection");
+            };
+            let cores: Vec<u8> = information
+                .by_core()
+                .iter()
+                .map(|(core, _)| *core)
+                .collect();
+            frames.push((id, timestamp_ms - WALL_CLOCK_START_MS, cores));
+        }
+        frames.len() < 6
+    });
+    assert_eq!(
+        frames,
+        [
+            (0, 0, vec![0]),
+            (1, 0, vec![1]),
+            (0, 1000, vec![0]),
+            (0, 2000, vec![0]),
+            (0, 3000, vec![0]),
+            (1, 3000, vec![1]),
+        ]
+    );
+}
+
+#[test]
+fn get_governor_reads_only_the_governor() {
+    let mut server = Server::new(HookedFs::new(fixture(&three_cores())));
+    let Response::Governors(governors) = server.handle(Request::GetGovernor(CpuCores::All)) else {
+        panic!("not governors");
+    };
+    let mut governors: Vec<(u8, String)> = governors.into_iter().collect();
+    governors.sort();
+    assert_eq!(
+        governors,
+        [
+            (0, "powersave".to_string()),
+            (1, "performance".to_string()),
+            (2, "powersave".to_string()),
+        ]
+    );
+    assert_eq!(server.fs.opens("scaling_governor"), 3);
+    assert_eq!(server.fs.opens("scaling_cur_freq"), 0);
+    assert_eq!(server.fs.opens("scaling_available_governors"), 0);
+}
+
+#[test]
+fn get_without_frequency_skips_cur_freq() {
+    let mut server = Server::new(HookedFs::new(fixture(&three_cores())));
+    let options = GetOptions {
+        include_frequency: false,
+        ..Default::default()
+    };
+    let request = Request::GetWithOptions(CpuCores::One(0), options);
+    let Response::Information(info) = server.handle(request) else {
+        panic!("not information");
+    };
+    let cores = info.by_core();
+    assert_eq!(cores[0].1.governor, "powersave");
+    assert_eq!(cores[0].1.megahertz, None);
+    assert_eq!(cores[0].1.khz, None);
+    assert_eq!(server.fs.opens("scaling_cur_freq"), 0);
+
+    let Response::Information(info) = server.handle(Request::Get(CpuCores::One(0))) else {
+        panic!("not information");
+    };
+    assert_eq!(info.by_core()[0].1.megahertz, Some(2400));
+    assert_eq!(server.fs.opens("scaling_cur_freq"), 1);
+}
+
+#[test]
+fn frequency_readings_keep_why_the_frequency_is_missing() {
+    let mut files = three_cores();
+    files.extend([
+        ("/sys/devices/system/cpu/present", "0-3\n"),
+        (
+            "/sys/devices/system/cpu/cpu2/cpufreq/scaling_cur_freq",
+            "n/a\n",
+        ),
+    ]);
+    let mut server = Server::new(fixture(&files));
+    let Response::Information(info) = server.handle(Request::Get(CpuCores::All)) else {
+        panic!("not information");
+    };
+    let readings: Vec<(u8, FrequencyReading, Option<u64>)> = info
+        .by_core()
+        .into_iter()
+        .map(|(core, info)| (core, info.frequency, info.megahertz))
+        .collect();
+    assert_eq!(
+        readings,
+        [
+            (0, FrequencyReading::Known(2_400_000), Some(2400)),
+            (1, FrequencyReading::Unavailable, None),
+            (2, FrequencyReading::Unparseable, None),
+            (3, FrequencyReading::Offline, None),
+        ]
+    );
+}
+
+#[test]
+fn apply_profile_sets_governor_epp_and_boost() {
+    let mut files = three_cores();
+    files.extend([
+        ("/sys/devices/system/cpu/cpufreq/boost", "1\n"),
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference",
+            "performance\n",
+        ),
+    ]);
+    let battery = Profile {
+        governor: Some("powersave".into()),
+        boost: Some(false),
+        epp: Some("power".into()),
+    };
+    let options = ServerOptions::default().profiles(HashMap::from([("battery".into(), battery)]));
+    let mut server = Server::with_clock(fixture(&files), options, MockClock::default());
+
+    let request = Request::ApplyProfile("battery".into());
+    assert!(matches!(server.handle(request), Response::Ack));
+    for core in 0..3 {
+        let governor = file(&server, &attr_path(core, "scaling_governor"));
+        assert_eq!(governor.as_deref(), Some("powersave"));
+    }
+    let epp = file(&server, &attr_path(0, "energy_performance_preference"));
+    assert_eq!(epp.as_deref(), Some("power"));
+    let epp = file(&server, &attr_path(1, "energy_performance_preference"));
+    assert_eq!(epp, None);
+    let boost = file(&server, "/sys/devices/system/cpu/cpufreq/boost");
+    assert_eq!(boost.as_deref(), Some("0"));
+
+    let request = Request::ApplyProfile("gaming".into());
+    assert!(matches!(
+        server.handle(request),
+        Response::Error(ServerError::UnknownProfile)
+    ));
+}
+
+#[test]
+fn list_profiles_names_and_summarizes_each_profile() {
+    let profiles = HashMap::from([
+        (
+            "gaming".to_string(),
+            Profile {
+                governor: Some("performance".into()),
+                boost: Some(true),
+                epp: None,
+            },
+        ),
+        (
+            "battery".to_string(),
+            Profile {
+                governor: Some("powersave".into()),
+                boost: Some(false),
+                epp: Some("power".into()),
+            },
+        ),
+    ]);
+    let options = ServerOptions::default().profiles(profiles);
+    let mut server = Server::with_clock(fixture(&three_cores()), options, MockClock::default());
+    let Response::Profiles(profiles) = server.handle(Request::ListProfiles) else {
+        panic!("not profiles");
+    };
+    assert_eq!(
+        profiles,
+        [
+            (
+                "battery".to_string(),
+                "governor powersave, boost off, epp power".to_string()
+            ),
+            (
+                "gaming".to_string(),
+                "governor performance, boost on".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn cores_without_sysfs_fall_back_to_proc_cpuinfo() {
+    let mut server = Server::new(fixture(&[
+        ("/sys/devices/system/cpu/present", "0-1\n"),
+        (
+            PROC_CPUINFO,
+            "processor\t: 0\ncpu MHz\t\t: 2400.000\n\nprocessor\t: 1\ncpu MHz\t\t: 1200.500\n",
+        ),
+    ]));
+    let Response::Information(info) = server.handle(Request::Get(CpuCores::All)) else {
+        panic!("not information");
+    };
+    let readings: Vec<(u8, FrequencyReading, bool)> = info
+        .by_core()
+        .into_iter()
+        .map(|(core, info)| (core, info.frequency, info.online))
+        .collect();
+    assert_eq!(
+        readings,
+        [
+            (0, FrequencyReading::Approximate(2_400_000), true),
+            (1, FrequencyReading::Approximate(1_200_500), true),
+        ]
+    );
+}
+
+#[test]
+fn min_max_with_min_above_max_writes_nothing() {
+    let mut server = Server::new(fixture(&limited_cores("acpi-cpufreq")));
+    let before = server.fs.0.clone();
+    let request = Request::Set(
+        CpuCores::All,
+        ScalingType::MinMax {
+            min_khz: 3_200_000,
+            max_khz: 2_000_000,
+        },
+    );
+    assert!(matches!(
+        server.handle(request),
+        Response::Error(ServerError::InvalidLimits)
+    ));
+    assert_eq!(server.fs.0, before);
+}
+
+#[test]
+fn probe_writes_detects_locked_firmware() {
+    let governor = attr_path(0, "scaling_governor");
+    // the firmware puts back its own governor after every write
+    let script: &[Option<&str>] = &[
+        Some("powersave\n"),
+        Some("powersave\n"),
+        Some("performance\n"),
+    ];
+    let mut server =
+        Server::new(HookedFs::new(fixture(&three_cores())).scripted(&[(&governor, script)]));
+    assert!(matches!(server.probe_writes(), Ok(false)));
+    let Response::Capabilities(caps) = server.handle(Request::Capabilities) else {
+        panic!("not capabilities");
+    };
+    assert_eq!(caps.writes_effective, Some(false));
+
+    let mut server = Server::new(fixture(&three_cores()));
+    assert!(matches!(server.probe_writes(), Ok(true)));
+    let governor = file(&server, &governor);
+    assert_eq!(governor.as_deref(), Some("powersave"));
+}
+
+#[test]
+fn probe_writes_reports_a_failed_write_back() {
+    let governor = attr_path(0, "scaling_governor");
+    // the file disappears between reading the governor and writing it back
+    let script: &[Option<&str>] = &[Some("powersave\n"), None];
+    let mut server =
+        Server::new(HookedFs::new(fixture(&three_cores())).scripted(&[(&governor, script)]));
+    assert!(matches!(
+        server.probe_writes(),
+        Err(ServerError::Other(e)) if e.kind() == ErrorKind::NotFound
+    ));
+    assert_eq!(server.state().lock().writes_effective, None);
+}
+
+#[test]
+fn subscribe_delta_only_sends_large_moves() {
+    let script: &[Option<&'static str>] = &[
+        Some("2400000\n"),
+        Some("2450000\n"),
+        Some("2550000\n"),
+        Some("3000000\n"),
+        Some("2950000\n"),
+        Some("1000000\n"),
+    ];
+    let cur_freq = "/sys/devices/system/cpu/cpu0/cpufreq/scaling_cur_freq";
+    let fs = HookedFs::new(fixture(&three_cores())).scripted(&[(cur_freq, script)]);
+    let mut server = Server::with_clock(fs, ServerOptions::default(), MockClock::default());
+    let mut frames = Vec::new();
+    server.serve(Request::SubscribeDelta(CpuCores::One(0), 200), |response| {
+        if let Response::Frame {
+            timestamp_ms,
+            frame,
+            ..
+        } = response
+        {
+            let Response::Information(info) = *frame else {
+                panic!("a delta frame that is not information");
+            };
+            let megahertz = info.by_core()[0].1.megahertz;
+            frames.push((timestamp_ms - WALL_CLOCK_START_MS, megahertz));
+        }
+        frames.len() < 3
+    });
+    let tick = MIN_POLL_INTERVAL.as_millis() as u64;
+    assert_eq!(
+        frames,
+        [
+            (0, Some(2400)),
+            (3 * tick, Some(3000)),
+            (5 * tick, Some(1000))
+        ]
+    );
+}
+
+#[test]
+fn tunables_are_found_in_either_layout() {
+    let get = || Request::GetTunable {
+        core: 0,
+        name: "up_threshold".into(),
+    };
+    let mut files = three_cores();
+    files.extend([
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+            "ondemand\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpufreq/ondemand/up_threshold",
+            "95\n",
+        ),
+    ]);
+    let mut server = Server::new(fixture(&files));
+    assert!(matches!(
+        server.handle(get()),
+        Response::Tunable(value) if value == "95"
+    ));
+
+    files.extend([
+        (
+            "/sys/devices/system/cpu/cpufreq/policy0/related_cpus",
+            "0-2\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpufreq/policy0/ondemand/up_threshold",
+            "80\n",
+        ),
+    ]);
+    let mut server = Server::new(fixture(&files));
+    assert!(matches!(
+        server.handle(get()),
+        Response::Tunable(value) if value == "80"
+    ));
+    let request = Request::SetTunable {
+        core: 0,
+        name: "up_threshold".into(),
+        value: "70".into(),
+    };
+    assert!(matches!(server.handle(request), Response::Ack));
+    let policy = "/sys/devices/system/cpu/cpufreq/policy0/ondemand/up_threshold";
+    assert_eq!(file(&server, policy).as_deref(), Some("70"));
+    let global = "/sys/devices/system/cpu/cpufreq/ondemand/up_threshold";
+    assert_eq!(file(&server, global).as_deref(), Some("95"));
+}
+
+#[test]
+fn get_config_reports_the_startup_options() {
+    let options = ServerOptions::default()
+        .verify_writes(false)
+        .min_change_interval(Some(Duration::from_secs(5)))
+        .boot_governors(Some(BootGovernors {
+            boot: "performance".into(),
+            runtime: "powersave".into(),
+            delay: Duration::from_secs(30),
+        }))
+        .request_log(Some("/var/log/cpufreqd.log".into()))
+        .read_retries(3)
+        .strict_cores(true)
+        .profiles(HashMap::from([
+            ("gaming".into(), Profile::default()),
+            ("battery".into(), Profile::default()),
+        ]))
+        .max_freq_caps(Some(MaxFreqCaps {
+            caps: HashMap::from([(1, 2_000_000), (0, 1_800_000)]),
+            interval: Duration::from_secs(1),
+        }));
+    let mut server = Server::with_clock(fixture(&three_cores()), options, MockClock::default());
+    let Response::Config(config) = server.handle(Request::GetConfig) else {
+        panic!("not a config");
+    };
+    assert_eq!(
+        config,
+        DaemonConfig {
+            verify_writes: false,
+            min_change_interval: Some(Duration::from_secs(5)),
+            boot_governors: Some((
+                "performance".into(),
+                "powersave".into(),
+                Duration::from_secs(30)
+            )),
+            request_log: Some("/var/log/cpufreqd.log".into()),
+            read_retries: 3,
+            detect_competitors: false,
+            strict_cores: true,
+            profiles: vec!["battery".into(), "gaming".into()],
+            thermal_guard: None,
+            max_freq_caps: Some((vec![(0, 1_800_000), (1, 2_000_000)], Duration::from_secs(1))),
+        }
+    );
+}
+
+#[test]
+fn subscribers_share_one_read_pass_per_tick() {
+    let clock = MockClock::default();
+    let mut first = Server::with_clock(
+        HookedFs::new(fixture(&three_cores())),
+        ServerOptions::default(),
+        clock.clone(),
+    );
+    let mut second = Server::with_clock(
+        HookedFs::new(fixture(&three_cores())),
+        ServerOptions::default(),
+        clock.clone(),
+    )
+    .with_state(first.state().clone());
+    let subscribe = || Request::Subscribe(vec![(CpuCores::All, Duration::from_secs(1))]);
+    // take one frame from each subscriber
+    let one_frame = |response: Response<io::Error>| !matches!(response, Response::Frame { .. });
+
+    first.serve(subscribe(), one_frame);
+    second.serve(subscribe(), one_frame);
+    assert_eq!(first.fs.opens("scaling_governor"), 3);
+    assert_eq!(second.fs.opens("scaling_governor"), 0);
+
+    clock.sleep(MIN_POLL_INTERVAL);
+    second.serve(subscribe(), one_frame);
+    first.serve(subscribe(), one_frame);
+    assert_eq!(first.fs.opens("scaling_governor"), 3);
+    assert_eq!(second.fs.opens("scaling_governor"), 3);
+}
+
+#[test]
+fn strict_cores_aborts_the_whole_set_on_a_missing_core() {
+    let mut files = three_cores();
+    files.push(("/sys/devices/system/cpu/online", "0-2\n"));
+    let set = || {
+        Request::Set(
+            CpuCores::Multiple(vec![0, 2, 5]),
+            ScalingType::Preset("performance".into()),
+        )
+    };
+
+    let options = ServerOptions::default().strict_cores(true);
+    let mut server = Server::with_clock(fixture(&files), options, MockClock::default());
+    let before = server.fs.0.clone();
+    assert!(matches!(
+        server.handle(set()),
+        Response::Error(ServerError::Other(e)) if e.kind() == ErrorKind::NotFound
+    ));
+    assert_eq!(server.fs.0, before);
+
+    // without it the missing core is left out and the others are still set
+    let mut server = Server::new(fixture(&files));
+    assert!(matches!(server.handle(set()), Response::Applied { .. }));
+    for core in [0, 2] {
+        let governor = file(&server, &attr_path(core, "scaling_governor"));
+        assert_eq!(governor.as_deref(), Some("performance"));
+    }
+}
+
+#[test]
+fn frequency_step_is_reported_for_an_even_table() {
+    let mut files = three_cores();
+    files.extend([
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_available_frequencies",
+            "2400000 1600000 800000\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu1/cpufreq/scaling_available_frequencies",
+            "2400000 2000000 800000\n",
+        ),
+    ]);
+    let mut server = Server::new(fixture(&files));
+    let Response::Information(info) = server.handle(Request::Get(CpuCores::All)) else {
+        panic!("not information");
+    };
+    let steps: Vec<Option<u64>> = info
+        .by_core()
+        .into_iter()
+        .map(|(_, info)| info.frequency_step_khz)
+        .collect();
+    assert_eq!(steps, [Some(800_000), None, None]);
+}
+
+#[test]
+fn capabilities_come_from_the_first_core_with_cpufreq() {
+    let capabilities = |files: &[(&str, &str)]| {
+        let mut server = Server::new(fixture(files));
+        let Response::Capabilities(caps) = server.handle(Request::Capabilities) else {
+            panic!("not capabilities");
+        };
+        caps
+    };
+    // cpu0 without cpufreq, as some hypervisors and big.LITTLE setups leave it
+    let files = [
+        ("/sys/devices/system/cpu/present", "0-1\n"),
+        ("/sys/devices/system/cpu/online", "0-1\n"),
+        (
+            "/sys/devices/system/cpu/cpu1/cpufreq/scaling_available_governors",
+            "performance schedutil\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu1/cpufreq/scaling_driver",
+            "cppc_cpufreq\n",
+        ),
+    ];
+    let caps = capabilities(&files);
+    assert_eq!(caps.governors, ["performance", "schedutil"]);
+    assert_eq!(caps.driver.as_deref(), Some("cppc_cpufreq"));
+
+    let caps = capabilities(&files[..2]);
+    assert!(caps.governors.is_empty());
+    assert_eq!(caps.driver, None);
+}
+
+#[test]
+fn pstate_switch_refreshes_governors_and_features() {
+    let epp = "/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference";
+    let mut files = three_cores();
+    files.extend([(PSTATE_STATUS, "active\n"), (epp, "balance_power\n")]);
+    let mut server = Server::new(fixture(&files));
+    let capabilities = |server: &mut Server<TestFs>| {
+        let Response::Capabilities(caps) = server.handle(Request::Capabilities) else {
+            panic!("not capabilities");
+        };
+        caps
+    };
+    let caps = capabilities(&mut server);
+    assert!(caps.features.energy_performance_preference);
+    assert_eq!(caps.governors, ["performance", "powersave"]);
+
+    let request = Request::SetPstateStatus("passive".into());
+    assert!(matches!(server.handle(request), Response::Ack));
+    // what the kernel does when intel_pstate goes passive: intel_cpufreq takes over with the
+    // generic governors and without EPP
+    server.fs.0.remove(Path::new(epp));
+    for core in 0..3 {
+        let available = attr_path(core, "scaling_available_governors");
+        server.fs.0.insert(
+            Arc::from(Path::new(&available)),
+            "performance powersave schedutil\n".into(),
+        );
+    }
+
+    assert!(matches!(
+        server.handle(Request::List(CpuCores::All)),
+        Response::ScalingGovernors(governors) if governors == ["performance", "powersave", "schedutil"]
+    ));
+    let caps = capabilities(&mut server);
+    assert!(!caps.features.energy_performance_preference);
+    assert_eq!(caps.governors, ["performance", "powersave", "schedutil"]);
+}
+
+#[test]
+fn set_reports_the_previous_governors_for_undo() {
+    let mut server = Server::new(fixture(&three_cores()));
+    let request = Request::Set(CpuCores::All, ScalingType::Preset("performance".into()));
+    let Response::Applied { previous } = server.handle(request) else {
+        panic!("not applied");
+    };
+    let mut previous: Vec<(u8, String)> = previous.into_iter().collect();
+    previous.sort();
+    assert_eq!(
+        previous,
+        [
+            (0, "powersave".to_string()),
+            (1, "performance".to_string()),
+            (2, "powersave".to_string()),
+        ]
+    );
+
+    // undo by setting each core back
+    for (core, governor) in previous {
+        let request = Request::Set(CpuCores::One(core), ScalingType::Preset(governor));
+        assert!(matches!(server.handle(request), Response::Applied { .. }));
+    }
+    let cpu0 = file(&server, &attr_path(0, "scaling_governor"));
+    assert_eq!(cpu0.as_deref(), Some("powersave"));
+}
+
+#[test]
+fn a_set_that_fails_part_way_rolls_back_the_cores_already_written() {
+    let available = attr_path(2, "scaling_available_governors");
+    let script: &[Option<&str>] = &[None];
+    let fs = HookedFs::new(fixture(&three_cores())).scripted(&[(&available, script)]);
+    let mut server = Server::new(fs);
+    let request = Request::Set(CpuCores::All, ScalingType::Preset("performance".into()));
+    assert!(matches!(
+        server.handle(request),
+        Response::Error(ServerError::Other(e)) if e.kind() == ErrorKind::NotFound
+    ));
+    let fs = server.fs.fs.borrow();
+    let governor = |core| fs.0[Path::new(&attr_path(core, "scaling_governor"))].trim();
+    assert_eq!(
+        [governor(0), governor(1), governor(2)],
+        ["powersave", "performance", "powersave"]
+    );
+}
+
+#[test]
+fn watch_external_reports_only_changes_the_server_did_not_make() {
+    let fs = HookedFs::new(fixture(&three_cores())).scripted(&[
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+            &[Some("powersave\n"), Some("performance\n")],
+        ),
+        (
+            "/sys/devices/system/cpu/cpu1/cpufreq/scaling_governor",
+            &[Some("performance\n"), Some("powersave\n")],
+        ),
+    ]);
+    let mut server = Server::with_clock(fs, ServerOptions::default(), MockClock::default());
+    let state = server.state().clone();
+    let events = state.lock().events.subscribe();
+    let mut changes = Vec::new();
+    server.serve(Request::WatchExternal(CpuCores::All), |response| {
+        let Response::Frame { frame, .. } = response else {
+            return true;
+        };
+        match *frame {
+            // the server itself moves cpu0 to performance, which the script makes the next read
+            Response::Governors(_) => state.lock().events.publish(Event::GovernorApplied {
+                core: 0,
+                governor: "performance".into(),
+            }),
+            Response::ExternalChange {
+                core,
+                previous,
+                current,
+            } => changes.push((core, previous, current)),
+            _ => panic!("unexpected frame"),
+        }
+        changes.is_empty()
+    });
+    assert_eq!(
+        changes,
+        [(1, "performance".to_string(), "powersave".to_string())]
+    );
+    let external: Vec<Event> = events
+        .try_iter()
+        .filter(|event| matches!(event, Event::ExternalChange { .. }))
+        .collect();
+    assert_eq!(
+        external,
+        [Event::ExternalChange {
+            core: 1,
+            previous: "performance".into(),
+            current: "powersave".into(),
+        }]
+    );
+}
+
+#[test]
+fn thermal_guard_lowers_max_freq_through_the_ceiling_and_restores_it() {
+    let mut files = limited_cores("acpi-cpufreq");
+    files.extend([
+        ("/sys/devices/system/cpu/present", "0\n"),
+        ("/sys/class/thermal/thermal_zone0/type", "x86_pkg_temp\n"),
+        ("/sys/class/thermal/thermal_zone0/temp", "70000\n"),
+    ]);
+    let guard = ThermalGuard {
+        ceiling_mc: 90_000,
+        hysteresis_mc: 8_000,
+        interval: Duration::from_secs(1),
+    };
+    let options = ServerOptions::default().thermal_guard(Some(guard));
+    let clock = MockClock::default();
+    let mut server = Server::with_clock(fixture(&files), options, clock.clone());
+    let events = server.state().lock().events.subscribe();
+    let mut max_freqs = Vec::new();
+    for temperature in [70_000, 90_000, 85_000, 82_500, 82_000, 85_000] {
+        server.fs.0.insert(
+            Arc::from(Path::new("/sys/class/thermal/thermal_zone0/temp")),
+            format!("{temperature}\n"),
+        );
+        server.thermal_guard().unwrap();
+        max_freqs.push(cpu0_scaling(&server).2);
+        clock.sleep(Duration::from_secs(1));
+    }
+    // engaged at the ceiling, eased off while cooling, released only at the hysteresis and not
+    // engaged again on the way back up below the ceiling
+    assert_eq!(
+        max_freqs,
+        [
+            "3000000", "400000", "1950000", "2725000", "3000000", "3000000"
+        ]
+    );
+    assert_eq!(
+        events.try_iter().collect::<Vec<_>>(),
+        [
+            Event::ThermalEngaged {
+                temperature_mc: 90_000
+            },
+            Event::ThermalReleased {
+                temperature_mc: 82_000
+            },
+        ]
+    );
+}
+
+#[test]
+fn list_tunables_returns_every_ondemand_tunable() {
+    let mut files = three_cores();
+    files.extend([
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+            "ondemand\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpufreq/ondemand/up_threshold",
+            "95\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpufreq/ondemand/sampling_rate",
+            "10000\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpufreq/ondemand/sampling_down_factor",
+            "1\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpufreq/ondemand/ignore_nice_load",
+            "0\n",
+        ),
+        // another governor's tunables are not listed
+        (
+            "/sys/devices/system/cpu/cpufreq/conservative/freq_step",
+            "5\n",
+        ),
+    ]);
+    let mut server = Server::new(fixture(&files));
+    let Response::Tunables(tunables) = server.handle(Request::ListTunables(0)) else {
+        panic!("not tunables");
+    };
+    let expected = [
+        ("ignore_nice_load", "0"),
+        ("sampling_down_factor", "1"),
+        ("sampling_rate", "10000"),
+        ("up_threshold", "95"),
+    ]
+    .map(|(name, value)| (name.to_string(), value.to_string()));
+    assert_eq!(tunables, expected);
+
+    let Response::Tunables(tunables) = server.handle(Request::ListTunables(1)) else {
+        panic!("not tunables");
+    };
+    assert!(tunables.is_empty());
+}
+
+#[test]
+fn empty_governors_file_lists_nothing_and_refuses_set() {
+    let mut files = three_cores();
+    files.extend([(
+        "/sys/devices/system/cpu/cpu0/cpufreq/scaling_available_governors",
+        "\n",
+    )]);
+    let mut server = Server::new(fixture(&files));
+    assert!(matches!(
+        server.handle(Request::List(CpuCores::One(0))),
+        Response::ScalingGovernors(governors) if governors.is_empty()
+    ));
+    let before = server.fs.0.clone();
+    let request = Request::Set(CpuCores::One(0), ScalingType::Preset("performance".into()));
+    assert!(matches!(
+        server.handle(request),
+        Response::Error(ServerError::NoGovernorsAvailable)
+    ));
+    assert_eq!(server.fs.0, before);
+}
+
+#[test]
+fn nmi_watchdog_is_read_and_set() {
+    let mut server = Server::new(fixture(&[(NMI_WATCHDOG, "1\n")]));
+    assert!(matches!(
+        server.handle(Request::GetNmiWatchdog),
+        Response::NmiWatchdog(true)
+    ));
+    assert!(matches!(
+        server.handle(Request::SetNmiWatchdog(false)),
+        Response::Ack
+    ));
+    assert_eq!(file(&server, NMI_WATCHDOG).as_deref(), Some("0"));
+    assert!(matches!(
+        server.handle(Request::GetNmiWatchdog),
+        Response::NmiWatchdog(false)
+    ));
+
+    let mut server = Server::new(fixture(&three_cores()));
+    assert!(matches!(
+        server.handle(Request::SetNmiWatchdog(false)),
+        Response::Error(ServerError::Unsupported)
+    ));
+    assert_eq!(file(&server, NMI_WATCHDOG), None);
+}
+
+#[test]
+fn subscriptions_get_distinct_ids_on_their_frames() {
+    let mut server = Server::with_clock(
+        fixture(&three_cores()),
+        ServerOptions::default(),
+        MockClock::default(),
+    );
+    let stream = |server: &mut Server<TestFs, MockClock>, cores| {
+        let mut ids = Vec::new();
+        let request = Request::Subscribe(vec![(cores, Duration::from_secs(1))]);
+        server.serve(request, |response| {
+            match response {
+                Response::Subscribed(id) => ids.push(id),
+                Response::Frame { id, .. } => ids.push(id),
+                _ => panic!("unexpected response"),
+            }
+            ids.len() < 3
+        });
+        ids
+    };
+    let first = stream(&mut server, CpuCores::One(0));
+    let second = stream(&mut server, CpuCores::One(1));
+    assert_eq!(first, [first[0]; 3]);
+    assert_eq!(second, [second[0]; 3]);
+    assert_ne!(first[0], second[0]);
+}
+
+#[test]
+fn unsubscribe_stops_the_frames_of_that_id() {
+    let clock = MockClock::default();
+    let mut server = Server::with_clock(
+        fixture(&three_cores()),
+        ServerOptions::default(),
+        clock.clone(),
+    );
+    // the thread reading the connection's next requests
+    let mut other = server.fork();
+    let mut subscription = None;
+    let mut frames = 0;
+    let request = Request::Subscribe(vec![(CpuCores::All, Duration::from_secs(1))]);
+    server.serve(request, |response| {
+        match response {
+            Response::Subscribed(id) => subscription = Some(id),
+            Response::Frame { id, .. } => {
+                assert_eq!(Some(id), subscription);
+                frames += 1;
+                if frames == 2 {
+                    let unsubscribe = Request::Unsubscribe(id);
+                    assert!(matches!(other.handle(unsubscribe), Response::Ack));
+                }
+            }
+            _ => panic!("unexpected response"),
+        }
+        // keep accepting, the stream has to end by itself
+        assert!(frames <= 2, "a frame after unsubscribing");
+        true
+    });
+    assert_eq!(frames, 2);
+    let unsubscribe = Request::Unsubscribe(subscription.unwrap());
+    assert!(matches!(
+        server.handle(unsubscribe),
+        Response::Error(ServerError::UnknownSubscription)
+    ));
+}
+
+#[test]
+fn unsubscribe_from_another_connection_is_rejected() {
+    let server = Server::new(fixture(&three_cores()));
+    let id = server.new_subscription();
+    let mut other = server.connect();
+    assert!(matches!(
+        other.handle(Request::Unsubscribe(id)),
+        Response::Error(ServerError::UnknownSubscription)
+    ));
+    assert!(server.state().lock().subscriptions.contains_key(&id));
+
+    // the connection's own streams can still be stopped, also from its other threads
+    let stream = other.fork();
+    let own = stream.new_subscription();
+    assert!(matches!(
+        other.handle(Request::Unsubscribe(own)),
+        Response::Ack
+    ));
+    assert!(matches!(
+        server.fork().handle(Request::Unsubscribe(id)),
+        Response::Ack
+    ));
+}
+
+#[test]
+fn max_freq_cap_is_restored_on_the_next_tick() {
+    let caps = MaxFreqCaps {
+        caps: HashMap::from([(0, 2_000_000)]),
+        interval: Duration::from_secs(5),
+    };
+    let options = ServerOptions::default().max_freq_caps(Some(caps));
+    let clock = MockClock::default();
+    let mut server = Server::with_clock(
+        fixture(&limited_cores("acpi-cpufreq")),
+        options,
+        clock.clone(),
+    );
+    let max_freq = attr_path(0, "scaling_max_freq");
+    let set_max = |server: &mut Server<TestFs, MockClock>, khz: &str| {
+        let path = Arc::from(Path::new(&max_freq));
+        server.fs.0.insert(path, format!("{khz}\n"));
+    };
+
+    server.enforce_max_freq_caps().unwrap();
+    assert_eq!(cpu0_scaling(&server).2, "2000000");
+
+    // another tool raises the maximum, which stays until the next tick
+    set_max(&mut server, "3000000");
+    clock.sleep(Duration::from_secs(1));
+    server.enforce_max_freq_caps().unwrap();
+    assert_eq!(cpu0_scaling(&server).2, "3000000");
+    clock.sleep(Duration::from_secs(4));
+    server.enforce_max_freq_caps().unwrap();
+    assert_eq!(cpu0_scaling(&server).2, "2000000");
+
+    // a maximum below the cap is left alone
+    set_max(&mut server, "1500000");
+    clock.sleep(Duration::from_secs(5));
+    server.enforce_max_freq_caps().unwrap();
+    assert_eq!(cpu0_scaling(&server).2, "1500000");
+}
+
+#[test]
+fn available_frequencies_only_when_requested() {
+    let mut files = three_cores();
+    files.push((
+        "/sys/devices/system/cpu/cpu0/cpufreq/scaling_available_frequencies",
+        "2400000 1600000 800000\n",
+    ));
+    let mut server = Server::new(fixture(&files));
+    let mut available = |options: GetOptions| {
+        let request = Request::GetWithOptions(CpuCores::One(0), options);
+        let Response::Information(info) = server.handle(request) else {
+            panic!("not information");
+        };
+        info.by_core()[0].1.available_frequencies.clone()
+    };
+    assert_eq!(available(GetOptions::default()), None);
+    let options = GetOptions {
+        include_available_frequencies: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        available(options),
+        Some(vec![2_400_000, 1_600_000, 800_000])
+    );
+}
+
+#[test]
+fn topology_with_an_offline_core() {
+    let mut server = Server::new(fixture(&[
+        ("/sys/devices/system/cpu/present", "0-3\n"),
+        ("/sys/devices/system/cpu/online", "0-1,3\n"),
+        (
+            "/sys/devices/system/cpu/cpu0/topology/physical_package_id",
+            "0\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu1/topology/physical_package_id",
+            "0\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu3/topology/physical_package_id",
+            "1\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpufreq/policy0/related_cpus",
+            "0 1\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpufreq/policy2/related_cpus",
+            "2 3\n",
+        ),
+    ]));
+    let Response::Topology(topology) = server.handle(Request::Topology) else {
+        panic!("not a topology");
+    };
+    assert_eq!(
+        topology,
+        Topology {
+            present: vec![0, 1, 2, 3],
+            online: vec![0, 1, 3],
+            packages: HashMap::from([(0, vec![0, 1]), (1, vec![3])]),
+            clusters: vec![vec![0, 1], vec![2, 3]],
+        }
+    );
+    assert!(
+        topology
+            .online
+            .iter()
+            .all(|core| topology.present.contains(core))
+    );
+    assert!(topology.present.len() > topology.online.len());
+}
+
+#[test]
+fn set_during_a_subscription_shows_in_the_next_frame() {
+    let fs = HookedFs::new(fixture(&three_cores()));
+    let clock = MockClock::default();
+    let connection = |state: &SharedState| {
+        Server::with_clock(fs.clone(), ServerOptions::default(), clock.clone())
+            .with_state(state.clone())
+    };
+    let mut subscriber = Server::with_clock(fs.clone(), ServerOptions::default(), clock.clone());
+    let mut setter = connection(subscriber.state());
+    let mut late_subscriber = connection(subscriber.state());
+    let cpu0_governor = |frame: Response<io::Error>| {
+        let Response::Selection { information, .. } = frame else {
+            panic!("a subscription frame that is not a selection");
+        };
+        information.by_core()[0].1.governor.clone()
+    };
+
+    let mut seen = Vec::new();
+    let request = || Request::Subscribe(vec![(CpuCores::One(0), Duration::from_secs(1))]);
+    subscriber.serve(request(), |response| {
+        let Response::Frame { frame, .. } = response else {
+            return true;
+        };
+        seen.push(cpu0_governor(*frame));
+        if seen.len() == 1 {
+            let set = Request::Set(CpuCores::One(0), ScalingType::Preset("performance".into()));
+            assert!(matches!(setter.handle(set), Response::Applied { .. }));
+            // in the same tick as the cached read, which the write must have dropped
+            late_subscriber.serve(request(), |response| {
+                let Response::Frame { frame, .. } = response else {
+                    return true;
+                };
+                assert_eq!(cpu0_governor(*frame), "performance");
+                false
+            });
+        }
+        seen.len() < 2
+    });
+    assert_eq!(seen, ["powersave", "performance"]);
+}
+
+#[test]
+fn identical_cores_are_only_collapsed_when_asked_to() {
+    let files = [
+        ("/sys/devices/system/cpu/present", "0-1\n"),
+        (
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+            "powersave\n",
+        ),
+        (
+            "/sys/devices/system/cpu/cpu1/cpufreq/scaling_governor",
+            "powersave\n",
+        ),
+    ];
+    let mut server = Server::new(fixture(&files));
+    let Response::Information(Information::Table(table)) =
+        server.handle(Request::Get(CpuCores::All))
+    else {
+        panic!("expected a table");
+    };
+    assert_eq!(table.len(), 2);
+    let request = Request::GetWithOptions(CpuCores::All, GetOptions::default());
+    assert!(matches!(
+        server.handle(request),
+        Response::Information(Information::Table(_))
+    ));
+
+    let options = GetOptions {
+        collapse: true,
+        ..Default::default()
+    };
+    let Response::Information(Information::Uniform { cores, info }) =
+        server.handle(Request::GetWithOptions(CpuCores::All, options))
+    else {
+        panic!("expected the uniform form");
+    };
+    assert!(matches!(cores, CpuCores::Range(0, 1)));
+    assert_eq!(info.governor, "powersave");
+}
@@ -0,0 +1,119 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use libcpufreq::SubscriptionId;
+
+use crate::events::EventBus;
+use crate::info::CoreInfo;
+
+/// mutable server state shared by every connection handler. handlers share one instance through a
+/// [SharedState] instead of each feature keeping its own `Arc<Mutex<..>>`
+#[derive(Debug, Default)]
+pub(crate) struct ServerState {
+    /// when each core's governor was last changed, in milliseconds from
+    /// [Clock::monotonic_ms](libcpufreq::Clock::monotonic_ms)
+    pub(crate) last_change: HashMap<u8, u64>,
+    /// outstanding confirmation tokens with the cores they confirm offlining and when they were
+    /// issued, in milliseconds from [Clock::monotonic_ms](libcpufreq::Clock::monotonic_ms)
+    pub(crate) confirmations: HashMap<u64, (Vec<u8>, u64)>,
+    /// cores waiting to revert to a fallback governor, see
+    /// [ScalingType::WithFallback](libcpufreq::ScalingType::WithFallback)
+    pub(crate) fallbacks: HashMap<u8, Fallback>,
+    /// the result of [Server::probe_writes](crate::server::Server::probe_writes), [None] until it
+    /// has run
+    pub(crate) writes_effective: Option<bool>,
+    /// the last information read for each core by a streaming request, with the poll tick it was
+    /// read in, so that streams polling in the same tick share one read
+    pub(crate) info_cache: HashMap<u8, (u64, CoreInfo)>,
+    /// bumped whenever [ServerState::info_cache] is invalidated. a stream reads sysfs without
+    /// holding the lock and only caches its read if the generation is the same as before it
+    /// started, so a read racing a write is never cached
+    pub(crate) info_generation: u64,
+    /// the `scaling_max_freq` of each core before the thermal guard lowered it, empty while the
+    /// guard is not engaged
+    pub(crate) thermal_limits: HashMap<u8, u64>,
+    /// when the thermal guard last checked the temperature, in milliseconds from
+    /// [Clock::monotonic_ms](libcpufreq::Clock::monotonic_ms)
+    pub(crate) thermal_checked_ms: Option<u64>,
+    /// when the `scaling_max_freq` caps were last checked, in milliseconds from
+    /// [Clock::monotonic_ms](libcpufreq::Clock::monotonic_ms)
+    pub(crate) caps_checked_ms: Option<u64>,
+    /// the last [SubscriptionId](libcpufreq::SubscriptionId) handed out, ids start at 1
+    pub(crate) last_subscription: u32,
+    /// the streams still running with the connection that started each, see
+    /// [Request::Unsubscribe](libcpufreq::Request::Unsubscribe)
+    pub(crate) subscriptions: HashMap<SubscriptionId, u64>,
+    /// the last connection id handed out by [Server::connect](crate::server::Server::connect)
+    pub(crate) last_connection: u64,
+    /// the thread siblings [Request::SetSmt](libcpufreq::Request::SetSmt) offlined, which are the
+    /// ones it onlines again
+    pub(crate) smt_offlined: BTreeSet<u8>,
+    /// notifications from handlers to subscription loops
+    pub(crate) events: EventBus,
+}
+
+impl ServerState {
+    /// drop the cached information of `core`, or of every core for [None], after a write made it
+    /// stale
+    pub(crate) fn invalidate_info(&mut self, core: Option<u8>) {
+        match core {
+            Some(core) => {
+                self.info_cache.remove(&core);
+            }
+            None => self.info_cache.clear(),
+        }
+        self.info_generation += 1;
+    }
+}
+
+/// a pending revert to a fallback governor
+#[derive(Debug, Clone)]
+pub(crate) struct Fallback {
+    pub(crate) governor: String,
+    /// when to revert, in milliseconds from [Clock::monotonic_ms](libcpufreq::Clock::monotonic_ms)
+    pub(crate) deadline_ms: u64,
+    /// how far a keepalive pushes the deadline out
+    pub(crate) after_ms: u64,
+}
+
+/// a handle to the [ServerState], cheap to clone into each handler
+#[derive(Debug, Default, Clone)]
+pub(crate) struct SharedState(Arc<Mutex<ServerState>>);
+
+impl SharedState {
+    /// lock the state. a handler that panicked while holding the lock cannot leave the state
+    /// half written in a way that matters, so poisoning is ignored
+    pub(crate) fn lock(&self) -> MutexGuard<'_, ServerState> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn handlers_on_two_threads_see_each_others_changes() {
+        let state = SharedState::default();
+        let handlers: Vec<_> = (0..2u8)
+            .map(|handler| {
+                let state = state.clone();
+                thread::spawn(move || {
+                    for i in 0..1000 {
+                        let mut state = state.lock();
+                        state.last_subscription += 1;
+                        state.last_change.insert(handler, i);
+                    }
+                })
+            })
+            .collect();
+        for handler in handlers {
+            handler.join().unwrap();
+        }
+        let state = state.lock();
+        assert_eq!(state.last_subscription, 2000);
+        assert_eq!(state.last_change, HashMap::from([(0, 999), (1, 999)]));
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use libcpufreq::Information;
+
+/// how often a watch re-reads the cpufreq files. changes that revert within this interval are not
+/// seen
+pub(crate) const MIN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// clock speeds are compared in buckets of this many megahertz so that jitter is not reported as a
+/// change
+pub(crate) const FREQUENCY_BUCKET_MHZ: u64 = 100;
+
+/// remembers what a watching client was last sent so that only changes are emitted
+#[derive(Debug, Default)]
+pub(crate) struct ChangeWatcher {
+    last: Option<HashMap<u8, (String, Option<u64>)>>,
+}
+
+impl ChangeWatcher {
+    /// `info` if a governor or frequency bucket differs from the last information passed in. the
+    /// first information is always returned
+    pub(crate) fn changed(&mut self, info: Information) -> Option<Information> {
+        let summary = summarize(&info);
+        if self.last.as_ref() == Some(&summary) {
+            return None;
+        }
+        self.last = Some(summary);
+        Some(info)
+    }
+}
+
+fn summarize(info: &Information) -> HashMap<u8, (String, Option<u64>)> {
+    let bucket = |mhz: Option<u64>| mhz.map(|mhz| mhz / FREQUENCY_BUCKET_MHZ);
+    match info {
+        Information::All(info) => {
+            HashMap::from([(0, (info.governor.clone(), bucket(info.megahertz)))])
+        }
+        information => information
+            .by_core()
+            .into_iter()
+            .map(|(core, info)| (core, (info.governor.clone(), bucket(info.megahertz))))
+            .collect(),
+    }
+}
+
+/// remembers the clock speeds a client was last sent so that only large moves are emitted, see
+/// [Request::SubscribeDelta](libcpufreq::Request::SubscribeDelta)
+#[derive(Debug)]
+pub(crate) struct DeltaWatcher {
+    threshold_mhz: u64,
+    last: Option<HashMap<u8, Option<u64>>>,
+}
+
+impl DeltaWatcher {
+    pub(crate) fn new(threshold_mhz: u64) -> Self {
+        Self {
+            threshold_mhz,
+            last: None,
+        }
+    }
+
+    /// `info` if any core's clock speed differs from the last information returned by more than
+    /// the threshold, or a core gained or lost its clock speed. the first information is always
+    /// returned
+    pub(crate) fn changed(&mut self, info: Information) -> Option<Information> {
+        let current = frequencies(&info);
+        if let Some(last) = &self.last {
+            let moved = current
+                .iter()
+                .any(|(core, mhz)| self.moved(last.get(core).copied().flatten(), *mhz));
+            if !moved {
+                return None;
+            }
+        }
+        self.last = Some(current);
+        Some(info)
+    }
+
+    fn moved(&self, last: Option<u64>, mhz: Option<u64>) -> bool {
+        match (last, mhz) {
+            (Some(last), Some(mhz)) => last.abs_diff(mhz) > self.threshold_mhz,
+            (last, mhz) => last.is_some() != mhz.is_some(),
+        }
+    }
+}
+
+fn frequencies(info: &Information) -> HashMap<u8, Option<u64>> {
+    match info {
+        Information::All(info) => HashMap::from([(0, info.megahertz)]),
+        information => information
+            .by_core()
+            .into_iter()
+            .map(|(core, info)| (core, info.megahertz))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libcpufreq::PerCpuInformation;
+
+    use super::*;
+
+    fn table(governor: &str, megahertz: u64) -> Information {
+        let info = PerCpuInformation {
+            governor: governor.into(),
+            megahertz: Some(megahertz),
+            online: true,
+            ..Default::default()
+        };
+        Information::Table(HashMap::from([(0, info)]))
+    }
+
+    #[test]
+    fn change_watcher_only_emits_changes() {
+        let mut watcher = ChangeWatcher::default();
+        assert!(watcher.changed(table("schedutil", 2010)).is_some());
+        assert!(watcher.changed(table("schedutil", 2010)).is_none());
+        // the same frequency bucket
+        assert!(watcher.changed(table("schedutil", 2090)).is_none());
+        assert!(watcher.changed(table("performance", 2090)).is_some());
+        assert!(watcher.changed(table("performance", 2090)).is_none());
+        assert!(watcher.changed(table("performance", 2100)).is_some());
+    }
+}
@@ -0,0 +1,45 @@
+//! what a system's cpufreq implementation supports
+
+use bincode::{Decode, Encode};
+
+/// which optional cpufreq files exist. newer kernels and some drivers expose files that others do
+/// not
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+pub struct FeatureSet {
+    /// `energy_performance_preference` (EPP) can be read and set
+    pub energy_performance_preference: bool,
+    /// `base_frequency` is reported
+    pub base_frequency: bool,
+    /// `scaling_setspeed` exists, frequencies can be set with the userspace governor
+    pub scaling_setspeed: bool,
+    /// `scaling_available_frequencies` lists the frequencies the driver supports
+    pub available_frequencies: bool,
+    /// turbo boost can be toggled through `cpufreq/boost` or `intel_pstate/no_turbo`
+    pub boost: bool,
+    /// the cpufreq statistics module is loaded (`cpufreq/stats`)
+    pub stats: bool,
+    /// the intel_pstate driver is present
+    pub intel_pstate: bool,
+    /// the ACPI platform profile can be read and set
+    pub platform_profile: bool,
+}
+
+/// a summary of what the server's system supports
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+pub struct Capabilities {
+    /// the cpufreq driver, eg `intel_pstate`
+    pub driver: Option<String>,
+    /// the governors available on the first online core with cpufreq support, empty if there is
+    /// none
+    pub governors: Vec<String>,
+    /// the optional files that exist
+    pub features: FeatureSet,
+    /// whether turbo boost is currently enabled, [None] if it cannot be toggled or read
+    pub boost_enabled: Option<bool>,
+    /// other frequency managers found running, eg `tuned`, whose writes may fight the server's.
+    /// empty if none were found or the server does not look for them
+    pub competitors: Vec<String>,
+    /// whether writes change anything. some firmware locks cpufreq so that writes succeed but are
+    /// ignored. [None] if the server has not checked or could not tell
+    pub writes_effective: Option<bool>,
+}
@@ -0,0 +1,42 @@
+//! time source abstraction
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// a source of time. time dependent behaviour (timestamps, intervals, backoff) goes through this
+/// trait so that it can be driven by a fake clock
+pub trait Clock {
+    /// the current wall clock time in milliseconds since the UNIX epoch, for timestamps. it can
+    /// jump when the system time is changed, so intervals are measured with
+    /// [Clock::monotonic_ms] instead
+    fn now_ms(&self) -> u64;
+    /// milliseconds since an arbitrary fixed point, never going backwards. only the difference
+    /// between two readings means anything
+    fn monotonic_ms(&self) -> u64;
+    /// block the current thread for `duration`
+    fn sleep(&self, duration: Duration);
+}
+
+/// [Clock] backed by the system clocks: [SystemTime] for [Clock::now_ms] and [Instant] for
+/// [Clock::monotonic_ms]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+
+    /// milliseconds since the first call in this process
+    fn monotonic_ms(&self) -> u64 {
+        static START: OnceLock<Instant> = OnceLock::new();
+        START.get_or_init(Instant::now).elapsed().as_millis() as u64
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration)
+    }
+}
@@ -0,0 +1,37 @@
+//! zlib compression of frame payloads, see [Features::compression]
+//!
+//! [Features::compression]: crate::Features::compression
+
+use std::io::{self, ErrorKind, Read, Write};
+
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+
+/// compress `input` with zlib. callers that need a gain compare the lengths
+pub(crate) fn compress(input: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::with_capacity(input.len() / 2), Compression::fast());
+    encoder
+        .write_all(input)
+        .and_then(|_| encoder.finish())
+        .expect("writing to a Vec does not fail")
+}
+
+/// undo [compress]. fails with [ErrorKind::InvalidData] on malformed input or if the output
+/// would be longer than `max_len`, which protects against tiny inputs that expand enormously
+pub(crate) fn decompress(input: &[u8], max_len: usize) -> io::Result<Vec<u8>> {
+    let invalid = |_| io::Error::new(ErrorKind::InvalidData, "malformed compressed frame");
+    let mut output = Vec::with_capacity(input.len().saturating_mul(2).min(max_len));
+    // one byte past the limit tells a payload of exactly max_len apart from a longer one
+    ZlibDecoder::new(input)
+        .take(max_len as u64 + 1)
+        .read_to_end(&mut output)
+        .map_err(invalid)?;
+    if output.len() > max_len {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "compressed frame too large",
+        ));
+    }
+    Ok(output)
+}
@@ -0,0 +1,33 @@
+//! the daemon's effective configuration
+
+use std::time::Duration;
+
+use bincode::{Decode, Encode};
+
+/// the settings a server is running with, see [Request::GetConfig](crate::Request::GetConfig)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+pub struct DaemonConfig {
+    /// governor writes are read back and fail if they did not stick
+    pub verify_writes: bool,
+    /// the shortest time allowed between two governor changes of the same core, [None] if
+    /// unlimited
+    pub min_change_interval: Option<Duration>,
+    /// the governor used while booting, the one switched to afterwards and the delay in between
+    pub boot_governors: Option<(String, String, Duration)>,
+    /// the file mutating requests are logged to
+    pub request_log: Option<String>,
+    /// how many times a missing file is re-read before giving up
+    pub read_retries: u32,
+    /// other frequency managers are looked for and reported
+    pub detect_competitors: bool,
+    /// selecting offline cores fails instead of leaving them out
+    pub strict_cores: bool,
+    /// the names of the configured profiles, sorted
+    pub profiles: Vec<String>,
+    /// the thermal guard's ceiling and hysteresis in millidegrees Celsius and how often it checks,
+    /// [None] if disabled
+    pub thermal_guard: Option<(u64, u64, Duration)>,
+    /// the `scaling_max_freq` cap of each capped core in kHz, sorted by core, and how often the
+    /// caps are enforced. [None] if disabled
+    pub max_freq_caps: Option<(Vec<(u8, u64)>, Duration)>,
+}
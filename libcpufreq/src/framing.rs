@@ -0,0 +1,322 @@
+//! length prefixed framing for the binary protocol
+//!
+//! every frame is a big endian `u32` payload length followed by the payload. a connection opens
+//! with a handshake, [offer_features] and [accept_features], that agrees on the optional
+//! [Features] both ends support. with [Features::compression] every payload starts with a flag
+//! byte saying whether the rest is compressed
+
+use std::io::{self, ErrorKind, Read, Write};
+
+use bincode::Encode;
+
+/// the largest payload [read_frame] accepts, protects against allocating for a corrupt length
+pub const MAX_FRAME_LEN: u32 = 16 * 1024 * 1024;
+
+/// the most capacity a [FrameEncoder] keeps once its frames are small again. the buffer grows as
+/// needed for a large frame but is shrunk back later so one large frame does not pin its memory
+pub const MAX_RETAINED_CAPACITY: usize = 64 * 1024;
+
+/// payloads at least this long are compressed when [Features::compression] was agreed on. shorter
+/// ones rarely shrink enough to be worth it
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// the flag byte of an uncompressed payload, see [Features::compression]
+const FLAG_RAW: u8 = 0;
+/// the flag byte of a compressed payload, see [Features::compression]
+const FLAG_COMPRESSED: u8 = 1;
+
+/// optional protocol features, agreed on by the handshake when a connection opens
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Features {
+    /// payloads of at least [COMPRESSION_THRESHOLD] bytes are compressed if that makes them
+    /// smaller, for remote clients on slow links. needs the `compression` cargo feature
+    pub compression: bool,
+}
+
+impl Features {
+    /// every feature this build supports
+    pub fn supported() -> Self {
+        Self {
+            compression: cfg!(feature = "compression"),
+        }
+    }
+
+    /// the features both `self` and `other` have
+    pub fn intersection(self, other: Self) -> Self {
+        Self {
+            compression: self.compression && other.compression,
+        }
+    }
+
+    fn to_bits(self) -> u8 {
+        u8::from(self.compression)
+    }
+
+    /// unknown bits, from a peer that knows newer features, are ignored
+    fn from_bits(bits: u8) -> Self {
+        Self {
+            compression: bits & 1 != 0,
+        }
+    }
+}
+
+/// the client side of the handshake, the first exchange on a connection: send the features the
+/// client wants and return the ones the server agreed to
+pub fn offer_features<S: Read + Write>(stream: &mut S, offer: Features) -> io::Result<Features> {
+    write_frame(stream, &[offer.to_bits()])?;
+    Ok(offer.intersection(read_handshake(stream)?))
+}
+
+/// the server side of [offer_features]: read the client's offer and answer with the features
+/// both it and `supported` have, which the connection then uses
+pub fn accept_features<S: Read + Write>(
+    stream: &mut S,
+    supported: Features,
+) -> io::Result<Features> {
+    let agreed = read_handshake(stream)?.intersection(supported);
+    write_frame(stream, &[agreed.to_bits()])?;
+    Ok(agreed)
+}
+
+fn read_handshake<R: Read>(reader: &mut R) -> io::Result<Features> {
+    match read_frame(reader)?.as_deref() {
+        Some([bits]) => Ok(Features::from_bits(*bits)),
+        Some(_) => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "malformed handshake",
+        )),
+        None => Err(ErrorKind::UnexpectedEof.into()),
+    }
+}
+
+/// how many frames in a row must fit in [MAX_RETAINED_CAPACITY] before a [FrameEncoder] gives
+/// back its extra capacity. this keeps a stream of large frames from reallocating every frame
+pub const SHRINK_AFTER_FRAMES: u32 = 16;
+
+/// encodes values into frames, reusing one buffer across frames instead of allocating per frame
+#[derive(Debug, Default)]
+pub struct FrameEncoder {
+    buf: Vec<u8>,
+    /// frames in a row that fit in [MAX_RETAINED_CAPACITY] while the buffer was larger
+    small_frames: u32,
+    features: Features,
+}
+
+impl FrameEncoder {
+    /// an encoder for a connection that agreed on `features`
+    pub fn with_features(features: Features) -> Self {
+        Self {
+            features,
+            ..Self::default()
+        }
+    }
+
+    /// encode `value` with bincode's standard configuration and write it as one frame
+    pub fn write<W: Write, T: Encode>(&mut self, writer: &mut W, value: &T) -> io::Result<()> {
+        self.buf.clear();
+        if self.features.compression {
+            self.buf.push(FLAG_RAW);
+        }
+        let result =
+            bincode::encode_into_std_write(value, &mut self.buf, bincode::config::standard())
+                .map_err(io::Error::other)
+                .and_then(|_| match self.compressed() {
+                    Some(compressed) => write_frame(writer, &compressed),
+                    None => write_frame(writer, &self.buf),
+                });
+        self.maybe_shrink();
+        result
+    }
+
+    /// the flagged payload in the buffer compressed, [None] if it should be sent as is
+    #[cfg(feature = "compression")]
+    fn compressed(&self) -> Option<Vec<u8>> {
+        if !self.features.compression || self.buf.len() <= COMPRESSION_THRESHOLD {
+            return None;
+        }
+        let mut compressed = vec![FLAG_COMPRESSED];
+        compressed.extend(crate::compress::compress(&self.buf[1..]));
+        (compressed.len() < self.buf.len()).then_some(compressed)
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn compressed(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// shrink the buffer once it has been far larger than the frames written for a while
+    fn maybe_shrink(&mut self) {
+        if self.buf.capacity() <= MAX_RETAINED_CAPACITY || self.buf.len() > MAX_RETAINED_CAPACITY {
+            self.small_frames = 0;
+            return;
+        }
+        self.small_frames += 1;
+        if self.small_frames >= SHRINK_AFTER_FRAMES {
+            self.buf.shrink_to(MAX_RETAINED_CAPACITY);
+            self.small_frames = 0;
+        }
+    }
+}
+
+/// write `payload` as one frame
+pub fn write_frame<W: Write>(writer: &mut W, payload: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(payload.len())
+        .ok()
+        .filter(|len| *len <= MAX_FRAME_LEN)
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "frame too large"))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
+
+/// read one frame.
+///
+/// returns `Ok(None)` if the stream ended cleanly between frames (the peer disconnected). a stream
+/// that ends part way through a length prefix or payload is an [ErrorKind::UnexpectedEof] error
+pub fn read_frame<R: Read>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut prefix = [0; 4];
+    let mut read = 0;
+    while read < prefix.len() {
+        match reader.read(&mut prefix[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    let len = u32::from_be_bytes(prefix);
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(ErrorKind::InvalidData, "frame too large"));
+    }
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
+
+/// read one frame written by a [FrameEncoder] for a connection that agreed on `features`, and
+/// return its payload with the flag byte removed and decompressed. like [read_frame] returns
+/// `Ok(None)` if the stream ended cleanly between frames
+pub fn read_payload<R: Read>(reader: &mut R, features: Features) -> io::Result<Option<Vec<u8>>> {
+    let Some(mut payload) = read_frame(reader)? else {
+        return Ok(None);
+    };
+    if !features.compression {
+        return Ok(Some(payload));
+    }
+    match payload.first() {
+        Some(&FLAG_RAW) => {
+            payload.remove(0);
+            Ok(Some(payload))
+        }
+        Some(&FLAG_COMPRESSED) => decompress(&payload[1..]).map(Some),
+        _ => Err(io::Error::new(ErrorKind::InvalidData, "unknown frame flag")),
+    }
+}
+
+#[cfg(feature = "compression")]
+fn decompress(payload: &[u8]) -> io::Result<Vec<u8>> {
+    crate::compress::decompress(payload, MAX_FRAME_LEN as usize)
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(_payload: &[u8]) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "compressed frame, built without the compression feature",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eof_between_frames_is_clean_and_mid_prefix_is_an_error() {
+        let mut stream = Vec::new();
+        write_frame(&mut stream, b"ack").unwrap();
+        let mut reader = stream.as_slice();
+        assert_eq!(
+            read_frame(&mut reader).unwrap().as_deref(),
+            Some(&b"ack"[..])
+        );
+        assert!(read_frame(&mut reader).unwrap().is_none());
+
+        let mut reader = &stream[..2];
+        let error = read_frame(&mut reader).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+
+        let mut reader = &stream[..5];
+        let error = read_frame(&mut reader).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn frames_decode_despite_buffer_reuse() {
+        // a large frame first, so that the smaller ones after it are written over its bytes and
+        // the buffer is shrunk part way through
+        let values: Vec<Vec<u32>> = [100_000]
+            .into_iter()
+            .chain((0..40).map(|i| (i * 37) % 500))
+            .map(|len| (0..len).collect())
+            .collect();
+        let mut encoder = FrameEncoder::default();
+        let mut stream = Vec::new();
+        for value in &values {
+            encoder.write(&mut stream, value).unwrap();
+        }
+        assert!(encoder.buf.capacity() <= MAX_RETAINED_CAPACITY);
+
+        let mut reader = stream.as_slice();
+        for value in &values {
+            let payload = read_frame(&mut reader).unwrap().unwrap();
+            let (decoded, _): (Vec<u32>, _) =
+                bincode::decode_from_slice(&payload, bincode::config::standard()).unwrap();
+            assert_eq!(&decoded, value);
+        }
+        assert!(read_frame(&mut reader).unwrap().is_none());
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn large_tables_round_trip_compressed_and_smaller() {
+        use std::collections::HashMap;
+
+        use crate::{Information, PerCpuInformation};
+
+        let table = || {
+            let table = (0..=255)
+                .map(|core| {
+                    let info = PerCpuInformation {
+                        governor: "schedutil".into(),
+                        megahertz: Some(2400),
+                        khz: Some(2_400_000),
+                        online: true,
+                        scaling_min_khz: Some(800_000),
+                        scaling_max_khz: Some(3_000_000),
+                        ..Default::default()
+                    };
+                    (core, info)
+                })
+                .collect::<HashMap<_, _>>();
+            Information::Table(table)
+        };
+        let mut plain = Vec::new();
+        FrameEncoder::default().write(&mut plain, &table()).unwrap();
+        let features = Features { compression: true };
+        let mut compressed = Vec::new();
+        FrameEncoder::with_features(features)
+            .write(&mut compressed, &table())
+            .unwrap();
+        assert!(compressed.len() < plain.len() / 2);
+
+        let payload = read_payload(&mut compressed.as_slice(), features)
+            .unwrap()
+            .unwrap();
+        let (decoded, _): (Information, _) =
+            bincode::decode_from_slice(&payload, bincode::config::standard()).unwrap();
+        let table = table();
+        assert!(decoded.by_core() == table.by_core());
+    }
+}
@@ -0,0 +1,225 @@
+//! frequency parsing
+
+use std::collections::HashMap;
+
+use bincode::{Decode, Encode};
+
+use crate::ParseError;
+
+/// a frequency read from sysfs, keeping why it is missing when it is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum FrequencyReading {
+    /// the frequency in kHz
+    Known(u64),
+    /// the core is offline
+    #[default]
+    Offline,
+    /// the file is missing or could not be read, eg because the driver does not provide it
+    Unavailable,
+    /// the file was read but does not hold a number
+    Unparseable,
+    /// an approximate frequency in kHz from `/proc/cpuinfo`, used when the kernel has no cpufreq
+    /// sysfs tree, see [parse_cpuinfo]
+    Approximate(u64),
+}
+
+impl FrequencyReading {
+    /// the frequency in kHz, if known, even approximately
+    pub fn khz(&self) -> Option<u64> {
+        match self {
+            Self::Known(khz) | Self::Approximate(khz) => Some(*khz),
+            _ => None,
+        }
+    }
+
+    /// the frequency in megahertz, truncated, if known
+    pub fn megahertz(&self) -> Option<u64> {
+        self.khz().map(|khz| khz / 1000)
+    }
+}
+
+/// parse the `cpu MHz` of each `processor` in `/proc/cpuinfo` into kHz. processors without a
+/// readable `cpu MHz`, eg on architectures that do not report it, are left out
+pub fn parse_cpuinfo(s: &str) -> HashMap<u8, u64> {
+    let mut frequencies = HashMap::new();
+    let mut processor = None;
+    for line in s.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        match key.trim() {
+            "processor" => processor = value.trim().parse().ok(),
+            "cpu MHz" => {
+                if let (Some(processor), Ok(khz)) =
+                    (processor, parse_frequency(&format!("{}MHz", value.trim())))
+                {
+                    frequencies.insert(processor, khz);
+                }
+            }
+            _ => {}
+        }
+    }
+    frequencies
+}
+
+/// parse a frequency such as `2.4GHz`, `2400MHz`, `1200000kHz` or `1200000` into kHz.
+///
+/// suffixes are case insensitive and a bare number is taken to be in kHz. decimals are accepted for
+/// every unit; anything below 1 kHz is truncated
+pub fn parse_frequency(s: &str) -> Result<u64, ParseError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(ParseError::Empty);
+    }
+
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = (&s[..split], s[split..].trim());
+
+    let invalid = || ParseError::InvalidNumber(number.into());
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(invalid());
+    }
+
+    let multiplier: u64 = match unit.to_ascii_lowercase().as_str() {
+        "" | "khz" => 1,
+        "mhz" => 1_000,
+        "ghz" => 1_000_000,
+        _ => return Err(ParseError::InvalidUnit(unit.into())),
+    };
+
+    let whole = match whole {
+        "" => 0,
+        whole => whole.parse::<u64>().map_err(|_| invalid())?,
+    };
+    let mut khz = whole.checked_mul(multiplier).ok_or_else(invalid)?;
+
+    // scale the fractional digits one at a time so that no precision is lost to floats
+    let mut scale = multiplier;
+    for digit in fraction.chars() {
+        let digit = digit.to_digit(10).ok_or_else(invalid)? as u64;
+        scale /= 10;
+        khz = khz.checked_add(digit * scale).ok_or_else(invalid)?;
+    }
+    Ok(khz)
+}
+
+/// the entry of `available` closest to `target_khz`, for drivers that only accept the frequencies
+/// listed in `scaling_available_frequencies`. ties go to the higher frequency. `available` does
+/// not need to be sorted; if it is empty `target_khz` is returned unchanged
+pub fn snap_to_available(target_khz: u64, available: &[u64]) -> u64 {
+    available
+        .iter()
+        .copied()
+        .min_by_key(|&khz| (khz.abs_diff(target_khz), std::cmp::Reverse(khz)))
+        .unwrap_or(target_khz)
+}
+
+/// whether a frequency table (eg `scaling_available_frequencies`) is ordered, either ascending or
+/// descending. equal neighbours are allowed
+pub fn is_monotonic(table: &[u64]) -> bool {
+    table.is_sorted() || table.iter().rev().is_sorted()
+}
+
+/// a frequency table sorted fastest first without duplicates, whatever order the driver lists it
+/// in
+pub fn normalize_frequency_table(mut table: Vec<u64>) -> Vec<u64> {
+    table.sort_unstable_by(|a, b| b.cmp(a));
+    table.dedup();
+    table
+}
+
+/// the spacing of a frequency table whose frequencies are evenly spaced, eg 100000 kHz for
+/// `800000 900000 1000000`, so sliders can snap to steps the driver accepts. [None] if the
+/// spacing is irregular or there are fewer than two distinct frequencies
+pub fn frequency_step(table: &[u64]) -> Option<u64> {
+    let table = normalize_frequency_table(table.to_vec());
+    let step = table.first()?.checked_sub(*table.get(1)?)?;
+    table
+        .windows(2)
+        .all(|pair| pair[0] - pair[1] == step)
+        .then_some(step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_frequency_units() {
+        assert_eq!(parse_frequency("1200000"), Ok(1_200_000));
+        assert_eq!(parse_frequency("1200000kHz"), Ok(1_200_000));
+        assert_eq!(parse_frequency("2400MHz"), Ok(2_400_000));
+        assert_eq!(parse_frequency("2.4GHz"), Ok(2_400_000));
+        assert_eq!(parse_frequency(" 2.4 ghz "), Ok(2_400_000));
+        assert_eq!(parse_frequency("800.5mhz"), Ok(800_500));
+    }
+
+    #[test]
+    fn parse_frequency_rejects_invalid_strings() {
+        assert_eq!(parse_frequency(""), Err(ParseError::Empty));
+        assert_eq!(
+            parse_frequency("2.4THz"),
+            Err(ParseError::InvalidUnit("THz".into()))
+        );
+        assert_eq!(
+            parse_frequency("GHz"),
+            Err(ParseError::InvalidNumber("".into()))
+        );
+        assert_eq!(
+            parse_frequency("1.2.3GHz"),
+            Err(ParseError::InvalidNumber("1.2.3".into()))
+        );
+    }
+
+    #[test]
+    fn snap_to_the_nearest_available_frequency() {
+        let available = [2_400_000, 800_000, 1_600_000];
+        assert_eq!(snap_to_available(1_600_000, &available), 1_600_000);
+        assert_eq!(snap_to_available(1_700_000, &available), 1_600_000);
+        assert_eq!(snap_to_available(2_300_000, &available), 2_400_000);
+        assert_eq!(snap_to_available(2_000_000, &available), 2_400_000);
+        assert_eq!(snap_to_available(5_000_000, &available), 2_400_000);
+        assert_eq!(snap_to_available(1_234_567, &[]), 1_234_567);
+    }
+
+    #[test]
+    fn normalize_an_unsorted_table() {
+        let table = vec![1_600_000, 2_400_000, 800_000, 2_400_000, 1_200_000];
+        assert!(!is_monotonic(&table));
+        let normalized = normalize_frequency_table(table);
+        assert_eq!(normalized, [2_400_000, 1_600_000, 1_200_000, 800_000]);
+        assert!(is_monotonic(&normalized));
+        assert!(is_monotonic(&[800_000, 800_000, 1_600_000]));
+        assert!(is_monotonic(&[]));
+    }
+
+    #[test]
+    fn parse_cpuinfo_with_two_cores() {
+        let cpuinfo = "processor\t: 0\nvendor_id\t: GenuineIntel\ncpu MHz\t\t: 2400.000\n\n\
+                       processor\t: 1\nvendor_id\t: GenuineIntel\ncpu MHz\t\t: 799.512\n\n";
+        assert_eq!(
+            parse_cpuinfo(cpuinfo),
+            HashMap::from([(0, 2_400_000), (1, 799_512)])
+        );
+        assert!(parse_cpuinfo("processor\t: 0\nBogoMIPS\t: 48.00\n").is_empty());
+    }
+
+    #[test]
+    fn frequency_step_of_even_and_irregular_tables() {
+        assert_eq!(
+            frequency_step(&[1_000_000, 800_000, 900_000, 1_100_000]),
+            Some(100_000)
+        );
+        assert_eq!(
+            frequency_step(&[2_400_000, 2_400_000, 1_200_000]),
+            Some(1_200_000)
+        );
+        assert_eq!(frequency_step(&[800_000, 900_000, 1_200_000]), None);
+        assert_eq!(frequency_step(&[800_000]), None);
+        assert_eq!(frequency_step(&[]), None);
+    }
+}
@@ -0,0 +1,141 @@
+//! scaling governors known to the kernel
+
+use std::fmt;
+
+use bincode::{Decode, Encode};
+
+/// a cpufreq scaling governor
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Encode, Decode)]
+pub enum Governor {
+    /// run at the maximum frequency
+    Performance,
+    /// run at the minimum frequency
+    Powersave,
+    /// frequency is set by userspace through `scaling_setspeed`
+    Userspace,
+    /// scale quickly with load
+    Ondemand,
+    /// scale gradually with load
+    Conservative,
+    /// scale with the scheduler's utilization data
+    Schedutil,
+    /// a governor this crate does not know about
+    Other(String),
+}
+
+impl Governor {
+    /// the name used by the kernel, eg `schedutil`
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Performance => "performance",
+            Self::Powersave => "powersave",
+            Self::Userspace => "userspace",
+            Self::Ondemand => "ondemand",
+            Self::Conservative => "conservative",
+            Self::Schedutil => "schedutil",
+            Self::Other(name) => name,
+        }
+    }
+
+    /// a one line explanation for newcomers, eg for tooltips
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Performance => "always runs at the highest allowed frequency",
+            Self::Powersave => "runs slowly, at the minimum or load based under pstate drivers",
+            Self::Userspace => "runs at a fixed frequency chosen by a user or program",
+            Self::Ondemand => "jumps to a high frequency under load and steps down when idle",
+            Self::Conservative => "like ondemand, but raises and lowers the frequency gradually",
+            Self::Schedutil => "follows the scheduler's view of cpu load, the usual default",
+            Self::Other(_) => "a custom or unknown governor",
+        }
+    }
+
+    /// whether the governor accepts a frequency written to `scaling_setspeed`. only
+    /// [Governor::Userspace] does
+    pub fn supports_setspeed(&self) -> bool {
+        matches!(self, Self::Userspace)
+    }
+}
+
+impl From<&str> for Governor {
+    fn from(name: &str) -> Self {
+        match name {
+            "performance" => Self::Performance,
+            "powersave" => Self::Powersave,
+            "userspace" => Self::Userspace,
+            "ondemand" => Self::Ondemand,
+            "conservative" => Self::Conservative,
+            "schedutil" => Self::Schedutil,
+            other => Self::Other(other.into()),
+        }
+    }
+}
+
+/// the kernel's name for a governor typed by a user, eg `powersave` for `PowerSave` or
+/// `power-save`. case and `-`, `_` and space separators are ignored when matching the governors
+/// [Governor] knows, other names are only trimmed since custom governors may be case sensitive
+pub fn normalize_governor_name(s: &str) -> String {
+    let name = s.trim();
+    let folded: String = name
+        .chars()
+        .filter(|c| !matches!(c, '-' | '_' | ' '))
+        .flat_map(char::to_lowercase)
+        .collect();
+    match Governor::from(folded.as_str()) {
+        Governor::Other(_) => name.into(),
+        governor => governor.as_str().into(),
+    }
+}
+
+impl fmt::Display for Governor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_userspace_supports_setspeed() {
+        assert!(Governor::Userspace.supports_setspeed());
+        for governor in [
+            Governor::Performance,
+            Governor::Powersave,
+            Governor::Ondemand,
+            Governor::Conservative,
+            Governor::Schedutil,
+            Governor::Other("interactive".into()),
+        ] {
+            assert!(!governor.supports_setspeed(), "{governor}");
+        }
+    }
+
+    #[test]
+    fn known_governors_have_distinct_descriptions() {
+        let known = [
+            Governor::Performance,
+            Governor::Powersave,
+            Governor::Userspace,
+            Governor::Ondemand,
+            Governor::Conservative,
+            Governor::Schedutil,
+        ];
+        let other = Governor::Other("interactive".into()).description();
+        let mut descriptions: Vec<&str> = known.iter().map(Governor::description).collect();
+        assert!(descriptions.iter().all(|d| !d.is_empty() && *d != other));
+        descriptions.sort_unstable();
+        descriptions.dedup();
+        assert_eq!(descriptions.len(), known.len());
+    }
+
+    #[test]
+    fn normalize_governor_names() {
+        assert_eq!(normalize_governor_name("PowerSave"), "powersave");
+        assert_eq!(normalize_governor_name(" power-save\n"), "powersave");
+        assert_eq!(normalize_governor_name("SCHED_UTIL"), "schedutil");
+        assert_eq!(normalize_governor_name("schedutil"), "schedutil");
+        assert_eq!(normalize_governor_name("  Interactive "), "Interactive");
+    }
+}
@@ -0,0 +1,159 @@
+//! newline-delimited JSON output for scripting
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::time::Duration;
+
+use crate::{
+    CpuCores, Features, FrameEncoder, Information, Request, Response, StringError, offer_features,
+    read_payload,
+};
+
+/// write `info` as one compact JSON object followed by a newline (NDJSON).
+///
+/// unlike pretty printed output every call emits exactly one line, so calling this once per sample
+/// produces a stream that can be piped into line oriented tools such as `jq`
+pub fn write_ndjson<W: Write>(writer: &mut W, info: &Information) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, info)?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+/// a line written by [subscribe_ndjson]
+#[derive(serde::Serialize)]
+struct Sample<'a> {
+    timestamp_ms: u64,
+    information: &'a Information,
+}
+
+/// open `connection`, a new stream to a server speaking the framed protocol (see
+/// [offer_features]), subscribe to `cores` every `interval` and write each sample to `out` as it
+/// arrives, as one compact JSON object per line with the sample's `timestamp_ms` and `information`.
+///
+/// returns once the server ends the stream. an error from the server is returned as an
+/// [io::Error] wrapping the [ServerError](crate::ServerError)
+pub fn subscribe_ndjson<C: Read + Write, W: Write>(
+    connection: &mut C,
+    cores: CpuCores,
+    interval: Duration,
+    out: &mut W,
+) -> io::Result<()> {
+    let features = offer_features(connection, Features::supported())?;
+    let request = Request::Subscribe(vec![(cores, interval)]);
+    FrameEncoder::with_features(features).write(connection, &request)?;
+    while let Some(payload) = read_payload(connection, features)? {
+        let (response, _): (Response<StringError>, _) =
+            bincode::decode_from_slice(&payload, bincode::config::standard())
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        let (timestamp_ms, frame) = match response {
+            Response::Subscribed(_) => continue,
+            Response::Frame {
+                timestamp_ms,
+                frame,
+                ..
+            } => (timestamp_ms, *frame),
+            Response::Error(e) => return Err(io::Error::other(e)),
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "not a stream frame")),
+        };
+        match frame {
+            Response::Selection { information, .. } => {
+                let sample = Sample {
+                    timestamp_ms,
+                    information: &information,
+                };
+                serde_json::to_writer(&mut *out, &sample)?;
+                out.write_all(b"\n")?;
+                out.flush()?;
+            }
+            Response::Error(e) => return Err(io::Error::other(e)),
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "not a selection")),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{PerCpuInformation, SubscriptionId, write_frame};
+
+    /// a connection whose server side has already sent `input`
+    struct Connection {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Connection {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Connection {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn sample(megahertz: u64) -> Response<StringError> {
+        let info = PerCpuInformation {
+            governor: "schedutil".into(),
+            megahertz: Some(megahertz),
+            online: true,
+            ..Default::default()
+        };
+        Response::Selection {
+            id: 0,
+            information: Information::Table(HashMap::from([(0, info)])),
+        }
+    }
+
+    #[test]
+    fn subscribe_ndjson_writes_a_line_per_sample() {
+        let mut input = Vec::new();
+        // the server agrees to no optional features
+        write_frame(&mut input, &[0]).unwrap();
+        let mut encoder = FrameEncoder::default();
+        let id = SubscriptionId(1);
+        encoder
+            .write(&mut input, &Response::<StringError>::Subscribed(id))
+            .unwrap();
+        for (timestamp_ms, megahertz) in [(1000, 2400), (1250, 2800)] {
+            let frame = Response::Frame {
+                id,
+                timestamp_ms,
+                frame: Box::new(sample(megahertz)),
+            };
+            encoder.write(&mut input, &frame).unwrap();
+        }
+        let mut connection = Connection {
+            input: Cursor::new(input),
+            output: Vec::new(),
+        };
+
+        let mut out = Vec::new();
+        subscribe_ndjson(
+            &mut connection,
+            CpuCores::One(0),
+            Duration::from_millis(250),
+            &mut out,
+        )
+        .unwrap();
+
+        let lines: Vec<&str> = std::str::from_utf8(&out).unwrap().lines().collect();
+        assert_eq!(lines.len(), 2);
+        for (line, (timestamp_ms, megahertz)) in lines.iter().zip([(1000, 2400), (1250, 2800)]) {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(value["timestamp_ms"], timestamp_ms);
+            let info = &value["information"]["Table"]["0"];
+            assert_eq!(info["governor"], "schedutil");
+            assert_eq!(info["megahertz"], megahertz);
+        }
+    }
+}
@@ -2,12 +2,82 @@
 
 #![warn(missing_docs)]
 
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, str::FromStr, time::Duration};
 
+use bincode::de::Decoder;
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
 use bincode::{Decode, Encode};
 
+mod capabilities;
+pub use capabilities::{Capabilities, FeatureSet};
+
+mod clock;
+pub use clock::{Clock, SystemClock};
+
+#[cfg(feature = "compression")]
+mod compress;
+
+mod framing;
+pub use framing::{
+    COMPRESSION_THRESHOLD, Features, FrameEncoder, MAX_FRAME_LEN, MAX_RETAINED_CAPACITY,
+    SHRINK_AFTER_FRAMES, accept_features, offer_features, read_frame, read_payload, write_frame,
+};
+
+mod config;
+pub use config::DaemonConfig;
+
+mod frequency;
+pub use frequency::{
+    FrequencyReading, frequency_step, is_monotonic, normalize_frequency_table, parse_cpuinfo,
+    parse_frequency, snap_to_available,
+};
+
+mod governor;
+pub use governor::{Governor, normalize_governor_name};
+
+mod platform;
+pub use platform::{ProfileRecommendation, recommend_for_profile};
+
+mod power;
+pub use power::{
+    HIGH_POWER_BUDGET_W, LOW_POWER_BUDGET_W, average_watts, governor_for_power_budget,
+};
+
+mod profile;
+pub use profile::{Profile, detect_active_profile};
+
+mod report;
+pub use report::format_status;
+
+mod retry;
+pub use retry::{RetryPolicy, send_with_retry};
+
+mod state;
+pub use state::{CoreState, CoreStateChange, CpuState, GovernorDrift, RestoreError, drift};
+
+mod stats;
+pub use stats::{CoreStats, parse_time_in_state};
+
+mod thermal;
+pub use thermal::{THERMAL_MARGIN_MC, max_freq_for_thermal};
+
+mod topology;
+pub use topology::Topology;
+
+mod validate;
+pub use validate::{Warning, validate_config};
+
+#[cfg(feature = "serde")]
+mod json;
+#[cfg(feature = "serde")]
+pub use json::{subscribe_ndjson, write_ndjson};
+
 #[derive(Encode, Decode)]
-/// response from a server implementation
+/// response from a server implementation.
+///
+/// variants are encoded by their index, so new variants must be added at the end to keep the wire
+/// format compatible
 pub enum Response<E: std::error::Error>{
     /// error
     Error(ServerError<E>),
@@ -15,28 +85,636 @@ pub enum Response<E: std::error::Error>{
     ScalingGovernors(Vec<String>),
     /// scaling information
     Information(Information),
+    /// the request was applied
+    Ack,
+    /// summaries keyed by physical package (socket) id
+    Packages(HashMap<u8, PackageInformation>),
+    /// the current ACPI platform profile, eg `balanced`
+    PlatformProfile(String),
+    /// the intel_pstate mode: `active`, `passive` or `off`
+    PstateStatus(String),
+    /// what the server's system supports
+    Capabilities(Capabilities),
+    /// the number of cores, see [Request::CoreCount]
+    CoreCount(u8),
+    /// every readable cpufreq attribute of a core, keyed by file name
+    Dump(HashMap<String, String>),
+    /// the change was rejected because a core changed too recently, nothing was applied
+    Deferred {
+        /// milliseconds until the change would be accepted
+        retry_after_ms: u64,
+    },
+    /// average watts per package over the sampling window, see [Request::GetPower]
+    Power(HashMap<u8, f64>),
+    /// nothing was applied, resend the request with this token to confirm it. tokens are single
+    /// use and expire after a minute
+    ConfirmationRequired(u64),
+    /// statistics keyed by core, see [Request::GetStats]
+    Stats(HashMap<u8, CoreStats>),
+    /// information for one selection of a [Request::Subscribe]
+    Selection {
+        /// the selection's index in the subscribe request
+        id: u32,
+        /// the selection's current information
+        information: Information,
+    },
+    /// governors keyed by core, see [Request::GetGovernor]
+    Governors(HashMap<u8, String>),
+    /// the name and [Profile::summary] of each configured profile sorted by name, see
+    /// [Request::ListProfiles]
+    Profiles(Vec<(String, String)>),
+    /// the value of a governor tunable, see [Request::GetTunable]
+    Tunable(String),
+    /// the server's effective configuration, see [Request::GetConfig]
+    Config(DaemonConfig),
+    /// the request was applied, see [Request::Set]
+    Applied {
+        /// the governor each changed core had before, to build an undo request from
+        previous: HashMap<u8, String>,
+    },
+    /// a core's governor was changed by something other than the server, see
+    /// [Request::WatchExternal]
+    ExternalChange {
+        /// the core
+        core: u8,
+        /// the governor before the change
+        previous: String,
+        /// the governor after the change
+        current: String,
+    },
+    /// the name and value of every tunable of a governor, see [Request::ListTunables]
+    Tunables(Vec<(String, String)>),
+    /// whether the NMI watchdog is enabled, see [Request::GetNmiWatchdog]
+    NmiWatchdog(bool),
+    /// a streaming request was accepted, its frames will carry this id. this is the first frame
+    /// of every stream
+    Subscribed(SubscriptionId),
+    /// a frame of the stream with this id, so that several streams can share a connection
+    Frame {
+        /// the stream, see [Response::Subscribed]
+        id: SubscriptionId,
+        /// when the frame was sent, in milliseconds since the UNIX epoch from the server's wall
+        /// clock ([Clock::now_ms]). the frames of a [Request::Subscribe] selection are sent as
+        /// soon as they are sampled, so they are about the selection's interval apart
+        timestamp_ms: u64,
+        /// the streamed response
+        frame: Box<Response<E>>,
+    },
+    /// the present and online cores and their grouping, see [Request::Topology]
+    Topology(Topology),
 }
 
+impl<E: std::error::Error + Encode> Response<E> {
+    /// the size in bytes of the response's frame payload with bincode's standard configuration, as
+    /// written by [FrameEncoder], without encoding it into a buffer. the length prefix is not
+    /// included. encoding can only fail in `E`'s [Encode] impl, the size is then counted up to the
+    /// failure
+    pub fn encoded_size(&self) -> usize {
+        let mut encoder = bincode::enc::EncoderImpl::new(
+            bincode::enc::write::SizeWriter::default(),
+            bincode::config::standard(),
+        );
+        let _ = self.encode(&mut encoder);
+        encoder.into_writer().bytes_written
+    }
+}
+
+impl<E: std::error::Error> Response<E> {
+    /// erase the concrete error type of [Response::Error], also inside a [Response::Frame], so the
+    /// response can be encoded whatever error type the server uses. see
+    /// [ServerError::to_string_error]
+    pub fn to_string_error(self) -> Response<StringError> {
+        match self {
+            Self::Error(e) => Response::Error(e.to_string_error()),
+            Self::ScalingGovernors(governors) => Response::ScalingGovernors(governors),
+            Self::Information(info) => Response::Information(info),
+            Self::Ack => Response::Ack,
+            Self::Packages(packages) => Response::Packages(packages),
+            Self::PlatformProfile(profile) => Response::PlatformProfile(profile),
+            Self::PstateStatus(status) => Response::PstateStatus(status),
+            Self::Capabilities(capabilities) => Response::Capabilities(capabilities),
+            Self::CoreCount(count) => Response::CoreCount(count),
+            Self::Dump(dump) => Response::Dump(dump),
+            Self::Deferred { retry_after_ms } => Response::Deferred { retry_after_ms },
+            Self::Power(power) => Response::Power(power),
+            Self::ConfirmationRequired(token) => Response::ConfirmationRequired(token),
+            Self::Stats(stats) => Response::Stats(stats),
+            Self::Selection { id, information } => Response::Selection { id, information },
+            Self::Governors(governors) => Response::Governors(governors),
+            Self::Profiles(profiles) => Response::Profiles(profiles),
+            Self::Tunable(value) => Response::Tunable(value),
+            Self::Config(config) => Response::Config(config),
+            Self::Applied { previous } => Response::Applied { previous },
+            Self::ExternalChange {
+                core,
+                previous,
+                current,
+            } => Response::ExternalChange {
+                core,
+                previous,
+                current,
+            },
+            Self::Tunables(tunables) => Response::Tunables(tunables),
+            Self::NmiWatchdog(enabled) => Response::NmiWatchdog(enabled),
+            Self::Subscribed(id) => Response::Subscribed(id),
+            Self::Frame {
+                id,
+                timestamp_ms,
+                frame,
+            } => Response::Frame {
+                id,
+                timestamp_ms,
+                frame: Box::new(frame.to_string_error()),
+            },
+            Self::Topology(topology) => Response::Topology(topology),
+        }
+    }
+}
+
+/// sentinel core index that stands for every core in per core views of [Information::All]
+pub const ALL_CORES: u8 = u8::MAX;
+
 /// frequency information returned from a server implementation
 #[derive(Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Information {
     /// information for all CPU cores
     All(PerCpuInformation),
     /// information for each individual CPU core
-    Table(HashMap<u8, PerCpuInformation>)
+    Table(HashMap<u8, PerCpuInformation>),
+    /// the same information for each of the cores, the compact form of a [Information::Table]
+    /// whose entries are all equal, see [Information::collapse] and [Information::expand]
+    Uniform {
+        /// the cores, [CpuCores::All] for every core
+        cores: CpuCores,
+        /// the information of each core
+        info: PerCpuInformation,
+    },
 }
 
-/// information for a given CPU core or for all CPUs
-#[derive(Encode, Decode)]
+impl Information {
+    /// a [Information::Table] whose entries are all equal as [Information::Uniform], so that it
+    /// is not sent once per core. anything else, including a table of a single core, is returned
+    /// as is
+    pub fn collapse(self) -> Self {
+        let Self::Table(table) = self else {
+            return self;
+        };
+        let mut infos = table.values();
+        let uniform = infos
+            .next()
+            .is_some_and(|first| table.len() > 1 && infos.all(|info| info == first));
+        if !uniform {
+            return Self::Table(table);
+        }
+        let mut cores: Vec<u8> = table.keys().copied().collect();
+        cores.sort_unstable();
+        let (first, last) = (cores[0], cores[cores.len() - 1]);
+        let cores = if usize::from(last - first) + 1 == cores.len() {
+            CpuCores::Range(first, last)
+        } else {
+            CpuCores::Multiple(cores)
+        };
+        let info = table.into_values().next().unwrap_or_default();
+        Self::Uniform { cores, info }
+    }
+
+    /// undo [Information::collapse], giving every core of a [Information::Uniform] its own
+    /// entry. `total` is the number of cores [CpuCores::All] stands for, as in
+    /// [CpuCores::resolve]
+    pub fn expand(self, total: u8) -> Self {
+        match self {
+            Self::Uniform { cores, info } => Self::Table(
+                cores
+                    .resolve(total)
+                    .into_iter()
+                    .map(|core| (core, info.clone()))
+                    .collect(),
+            ),
+            information => information,
+        }
+    }
+
+    /// `(core, information)` pairs sorted by core. [Information::All] and a
+    /// [Information::Uniform] over [CpuCores::All] give a single pair for [ALL_CORES]
+    pub fn by_core(&self) -> Vec<(u8, &PerCpuInformation)> {
+        let mut cores: Vec<(u8, &PerCpuInformation)> = match self {
+            Self::All(info)
+            | Self::Uniform {
+                cores: CpuCores::All,
+                info,
+            } => vec![(ALL_CORES, info)],
+            Self::Table(table) => table.iter().map(|(core, info)| (*core, info)).collect(),
+            Self::Uniform { cores, info } => cores
+                .resolve(0)
+                .into_iter()
+                .map(|core| (core, info))
+                .collect(),
+        };
+        cores.sort_unstable_by_key(|(core, _)| *core);
+        cores
+    }
+
+    /// the distinct governors in use by online cores, sorted. cores without a governor (see
+    /// [PerCpuInformation::governor]) are left out
+    pub fn governors(&self) -> Vec<&str> {
+        let mut governors: Vec<&str> = match self {
+            Self::All(info) => vec![&info.governor],
+            Self::Table(table) => table
+                .values()
+                .filter(|info| info.online)
+                .map(|info| info.governor.as_str())
+                .collect(),
+            Self::Uniform { info, .. } => Some(info.governor.as_str())
+                .filter(|_| info.online)
+                .into_iter()
+                .collect(),
+        };
+        governors.retain(|governor| !governor.is_empty());
+        governors.sort_unstable();
+        governors.dedup();
+        governors
+    }
+
+    /// `(core, governor)` pairs of the online cores with a governor, sorted by core.
+    /// [Information::All] gives a single pair for [ALL_CORES]
+    pub fn governors_by_core(&self) -> Vec<(u8, &str)> {
+        match self {
+            Self::All(info) => vec![(ALL_CORES, info.governor.as_str())],
+            information => information
+                .by_core()
+                .into_iter()
+                .filter(|(_, info)| info.online && !info.governor.is_empty())
+                .map(|(core, info)| (core, info.governor.as_str()))
+                .collect(),
+        }
+    }
+
+    /// the average clock speed in megahertz over the cores that report one
+    pub fn average_mhz(&self) -> Option<f64> {
+        let readings: Vec<u64> = match self {
+            Self::All(info) | Self::Uniform { info, .. } => info.megahertz.into_iter().collect(),
+            Self::Table(table) => table.values().filter_map(|info| info.megahertz).collect(),
+        };
+        if readings.is_empty() {
+            return None;
+        }
+        Some(readings.iter().sum::<u64>() as f64 / readings.len() as f64)
+    }
+
+    /// [Information::average_mhz] with each core weighted, eg by cluster size or maximum frequency
+    /// on hybrid CPUs. cores missing from `weights` weigh 1.0, so empty weights give the plain
+    /// average. [None] if no core reports a clock speed or the weights add up to 0
+    pub fn weighted_average_mhz(&self, weights: &HashMap<u8, f64>) -> Option<f64> {
+        let readings: Vec<(f64, u64)> = match self {
+            Self::All(info) | Self::Uniform { info, .. } => {
+                info.megahertz.map(|mhz| (1.0, mhz)).into_iter().collect()
+            }
+            Self::Table(table) => table
+                .iter()
+                .filter_map(|(core, info)| {
+                    Some((weights.get(core).copied().unwrap_or(1.0), info.megahertz?))
+                })
+                .collect(),
+        };
+        let total: f64 = readings.iter().map(|(weight, _)| weight).sum();
+        if readings.is_empty() || total == 0.0 {
+            return None;
+        }
+        let sum: f64 = readings
+            .iter()
+            .map(|(weight, mhz)| weight * *mhz as f64)
+            .sum();
+        Some(sum / total)
+    }
+
+    /// `(core, megahertz)` pairs sorted fastest first, cores without a clock speed are skipped.
+    /// ties are ordered by core. [Information::All] has no individual cores and yields nothing
+    pub fn cores_by_frequency(&self) -> Vec<(u8, u64)> {
+        let mut cores: Vec<(u8, u64)> = self
+            .by_core()
+            .into_iter()
+            .filter(|(core, _)| *core != ALL_CORES)
+            .filter_map(|(core, info)| Some((core, info.megahertz?)))
+            .collect();
+        cores.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        cores
+    }
+
+    /// how much more frequency is available system wide, as a percentage of the hardware maximum
+    /// (`cpuinfo_max_freq`) summed over the cores that report both a clock speed and a maximum
+    pub fn headroom_percent(&self) -> Option<f64> {
+        let cores: Vec<&PerCpuInformation> = match self {
+            Self::All(info) | Self::Uniform { info, .. } => vec![info],
+            Self::Table(table) => table.values().collect(),
+        };
+        let (current, max) = cores
+            .iter()
+            .filter_map(|info| Some((info.megahertz? * 1000, info.cpuinfo_max_khz?)))
+            .fold((0, 0), |(current, max), (khz, max_khz)| {
+                (current + khz, max + max_khz)
+            });
+        if max == 0 {
+            return None;
+        }
+        Some(max.saturating_sub(current) as f64 / max as f64 * 100.0)
+    }
+
+    /// whether every online core is pinned to one frequency, with `scaling_min_freq` equal to
+    /// `scaling_max_freq`, eg to warn that frequencies are locked. a core without both limits
+    /// counts as not pinned, and so does information without online cores
+    pub fn all_pinned(&self) -> bool {
+        let cores: Vec<&PerCpuInformation> = self
+            .by_core()
+            .into_iter()
+            .map(|(_, info)| info)
+            .filter(|info| info.online)
+            .collect();
+        !cores.is_empty()
+            && cores.iter().all(|info| {
+                info.scaling_min_khz.is_some() && info.scaling_min_khz == info.scaling_max_khz
+            })
+    }
+
+    /// a hash of each core's governor and scaling limits and of the turbo `boost` state, eg from
+    /// [Capabilities::boost_enabled], that is stable across restarts and builds, for cheaply
+    /// telling whether the configuration changed. volatile readings such as the clock speed are
+    /// left out. a [Information::Uniform] hashes like the equivalent [Information::Table]
+    pub fn config_hash(&self, boost: Option<bool>) -> u64 {
+        // FNV-1a, since std's hashers may change between releases
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut write = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= u64::from(*byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        write(&[u8::from(boost.is_some()), u8::from(boost == Some(true))]);
+        for (core, info) in self.by_core() {
+            write(&[core, u8::from(info.online)]);
+            write(&(info.governor.len() as u64).to_le_bytes());
+            write(info.governor.as_bytes());
+            for khz in [info.scaling_min_khz, info.scaling_max_khz] {
+                write(&[u8::from(khz.is_some())]);
+                write(&khz.unwrap_or(0).to_le_bytes());
+            }
+        }
+        hash
+    }
+
+    /// the governor `core` actually runs, which is its policy leader's since related cores
+    /// (`related_cpus`) share one. `related` maps a core to the cores of its policy, and a core
+    /// missing from it is its own leader. the leader is the lowest online related core. [None] if
+    /// no related core is online or in the table
+    pub fn effective_governor(&self, core: u8, related: &HashMap<u8, Vec<u8>>) -> Option<&str> {
+        let table = match self {
+            Self::All(info) => return Some(&info.governor),
+            Self::Uniform { info, .. } => {
+                return Some(info.governor.as_str()).filter(|_| info.online);
+            }
+            Self::Table(table) => table,
+        };
+        let leader = match related.get(&core) {
+            Some(related) => related
+                .iter()
+                .filter_map(|core| table.get(core).filter(|info| info.online).map(|_| *core))
+                .min()?,
+            None => core,
+        };
+        table
+            .get(&leader)
+            .filter(|info| info.online)
+            .map(|info| info.governor.as_str())
+    }
+}
+
+/// one line summary, eg `4 cores, governors: [schedutil], avg 2.4 GHz`
+impl fmt::Display for Information {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::All(_)
+            | Self::Uniform {
+                cores: CpuCores::All,
+                ..
+            } => write!(f, "all cores")?,
+            Self::Table(table) if table.len() == 1 => write!(f, "1 core")?,
+            Self::Table(table) => write!(f, "{} cores", table.len())?,
+            Self::Uniform { cores, .. } => write!(f, "{} cores", cores.resolve(0).len())?,
+        }
+        write!(f, ", governors: [{}]", self.governors().join(", "))?;
+        match self.average_mhz() {
+            Some(mhz) => write!(f, ", avg {:.1} GHz", mhz / 1000.0),
+            None => write!(f, ", avg unknown"),
+        }
+    }
+}
+
+/// information for a given CPU core or for all CPUs. the default value describes an offline CPU.
+///
+/// on the wire the optional fields follow a bitmask of the ones that are [Some], and only those
+/// are encoded, so fields a client did not ask for cost nothing. a new optional field takes the
+/// next bit
+#[derive(Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct PerCpuInformation {
-    /// the current scaling governor for this CPU
+    /// the current scaling governor for this CPU. empty if the core has no cpufreq support and
+    /// is only known to be online, eg from `/proc/cpuinfo`
     pub governor: String,
-    /// the clock speed in megahertz
+    /// the clock speed in megahertz, truncated from [PerCpuInformation::khz]
     pub megahertz: Option<u64>,
+    /// the exact clock speed (`scaling_cur_freq`) in kHz. servers fill in both this and
+    /// [PerCpuInformation::megahertz]
+    pub khz: Option<u64>,
+    /// whether the CPU was online when the information was gathered. an offline CPU has an empty
+    /// governor and no clock speed
+    pub online: bool,
+    /// the lowest frequency the governor may select (`scaling_min_freq`) in kHz
+    pub scaling_min_khz: Option<u64>,
+    /// the highest frequency the governor may select (`scaling_max_freq`) in kHz
+    pub scaling_max_khz: Option<u64>,
+    /// the lowest frequency the hardware supports (`cpuinfo_min_freq`) in kHz
+    pub cpuinfo_min_khz: Option<u64>,
+    /// the highest frequency the hardware supports (`cpuinfo_max_freq`) in kHz
+    pub cpuinfo_max_khz: Option<u64>,
+    /// the logical CPUs sharing this CPU's physical core (`topology/thread_siblings_list`),
+    /// including itself. changing the frequency of one sibling affects the others
+    pub thread_siblings: Option<Vec<u8>>,
+    /// the frequency measured by the hardware (`cpuinfo_cur_freq`) in kHz. only filled in when
+    /// requested with [GetOptions::include_measured]. under `userspace` this can differ from
+    /// [PerCpuInformation::khz], which reflects the requested frequency
+    pub measured_khz: Option<u64>,
+    /// the guaranteed, non turbo frequency (`base_frequency`) in kHz. only some drivers, such as
+    /// `intel_pstate`, report it
+    pub base_frequency_khz: Option<u64>,
+    /// how long the core has spent at each frequency (`stats/time_in_state`), as `(kHz, time in
+    /// 10ms units)` pairs. only filled in when requested with [GetOptions::include_time_in_state].
+    /// [None] without the cpufreq stats module
+    pub time_in_state: Option<Vec<(u64, u64)>>,
+    /// the current frequency (`scaling_cur_freq`) with the reason it is missing, if it is.
+    /// [PerCpuInformation::khz] and [PerCpuInformation::megahertz] are derived from it
+    pub frequency: FrequencyReading,
+    /// the spacing of `scaling_available_frequencies` in kHz when it is even, see
+    /// [frequency_step]. [None] for drivers without a frequency table or with uneven steps
+    pub frequency_step_khz: Option<u64>,
+    /// the frequencies in kHz the driver offers (`scaling_available_frequencies`), eg for the stops
+    /// of a slider. only filled in when requested with
+    /// [GetOptions::include_available_frequencies]. [None] for drivers without a frequency table
+    pub available_frequencies: Option<Vec<u64>>,
+}
+
+impl PerCpuInformation {
+    /// the current frequency as a fraction of the base frequency. above 1.0 the core is running
+    /// in turbo. [None] if either frequency is unknown or the base frequency is 0
+    pub fn turbo_ratio(&self) -> Option<f64> {
+        let base = self.base_frequency_khz.filter(|base| *base > 0)?;
+        Some(self.khz? as f64 / base as f64)
+    }
+
+    /// the header for [PerCpuInformation::to_csv_row], without a trailing newline
+    pub fn csv_header() -> &'static str {
+        "core,governor,mhz,scaling_min_khz,scaling_max_khz"
+    }
+
+    /// the core's governor, clock speed and scaling limits as a CSV row for `cpufreq dump --csv`,
+    /// in the column order of [PerCpuInformation::csv_header] and without a trailing newline.
+    /// unknown values are left blank
+    pub fn to_csv_row(&self, core: u8) -> String {
+        let cell = |value: Option<u64>| value.map_or(String::new(), |value| value.to_string());
+        format!(
+            "{core},{},{},{},{}",
+            self.governor,
+            cell(self.megahertz),
+            cell(self.scaling_min_khz),
+            cell(self.scaling_max_khz)
+        )
+    }
+}
+
+impl Encode for PerCpuInformation {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.governor.encode(encoder)?;
+        self.online.encode(encoder)?;
+        self.frequency.encode(encoder)?;
+        let present = [
+            self.megahertz.is_some(),
+            self.khz.is_some(),
+            self.scaling_min_khz.is_some(),
+            self.scaling_max_khz.is_some(),
+            self.cpuinfo_min_khz.is_some(),
+            self.cpuinfo_max_khz.is_some(),
+            self.thread_siblings.is_some(),
+            self.measured_khz.is_some(),
+            self.base_frequency_khz.is_some(),
+            self.time_in_state.is_some(),
+            self.frequency_step_khz.is_some(),
+            self.available_frequencies.is_some(),
+        ];
+        let mask = (0..).zip(present).fold(0u16, |mask, (bit, present)| {
+            mask | u16::from(present) << bit
+        });
+        mask.encode(encoder)?;
+        encode_present(&self.megahertz, encoder)?;
+        encode_present(&self.khz, encoder)?;
+        encode_present(&self.scaling_min_khz, encoder)?;
+        encode_present(&self.scaling_max_khz, encoder)?;
+        encode_present(&self.cpuinfo_min_khz, encoder)?;
+        encode_present(&self.cpuinfo_max_khz, encoder)?;
+        encode_present(&self.thread_siblings, encoder)?;
+        encode_present(&self.measured_khz, encoder)?;
+        encode_present(&self.base_frequency_khz, encoder)?;
+        encode_present(&self.time_in_state, encoder)?;
+        encode_present(&self.frequency_step_khz, encoder)?;
+        encode_present(&self.available_frequencies, encoder)
+    }
+}
+
+impl<Context> Decode<Context> for PerCpuInformation {
+    fn decode<D: Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let governor = String::decode(decoder)?;
+        let online = bool::decode(decoder)?;
+        let frequency = FrequencyReading::decode(decoder)?;
+        let mask = u16::decode(decoder)?;
+        // the fields after an unknown one cannot be found, its encoding is not known
+        if mask >> 12 != 0 {
+            return Err(DecodeError::Other("unknown PerCpuInformation fields"));
+        }
+        // fields are initialised in order, which is the order they were encoded in
+        Ok(Self {
+            governor,
+            online,
+            frequency,
+            megahertz: decode_present(mask, 0, decoder)?,
+            khz: decode_present(mask, 1, decoder)?,
+            scaling_min_khz: decode_present(mask, 2, decoder)?,
+            scaling_max_khz: decode_present(mask, 3, decoder)?,
+            cpuinfo_min_khz: decode_present(mask, 4, decoder)?,
+            cpuinfo_max_khz: decode_present(mask, 5, decoder)?,
+            thread_siblings: decode_present(mask, 6, decoder)?,
+            measured_khz: decode_present(mask, 7, decoder)?,
+            base_frequency_khz: decode_present(mask, 8, decoder)?,
+            time_in_state: decode_present(mask, 9, decoder)?,
+            frequency_step_khz: decode_present(mask, 10, decoder)?,
+            available_frequencies: decode_present(mask, 11, decoder)?,
+        })
+    }
+}
+
+bincode::impl_borrow_decode!(PerCpuInformation);
+
+/// encode the value of a [PerCpuInformation] field if it has one, see its [Encode] impl
+fn encode_present<T: Encode, E: Encoder>(
+    field: &Option<T>,
+    encoder: &mut E,
+) -> Result<(), EncodeError> {
+    field.as_ref().map_or(Ok(()), |value| value.encode(encoder))
+}
+
+/// decode a [PerCpuInformation] field if `bit` of `mask` says it was encoded
+fn decode_present<T: Decode<C>, C, D: Decoder<Context = C>>(
+    mask: u16,
+    bit: u32,
+    decoder: &mut D,
+) -> Result<Option<T>, DecodeError> {
+    if mask & 1 << bit == 0 {
+        return Ok(None);
+    }
+    T::decode(decoder).map(Some)
 }
 
-/// errors returned from a server request
+/// summary of the online cores of one physical package (socket)
 #[derive(Encode, Decode)]
+pub struct PackageInformation {
+    /// the distinct governors in use, sorted
+    pub governors: Vec<String>,
+    /// the average clock speed in megahertz of the cores that report one
+    pub average_mhz: Option<f64>,
+    /// the package's cumulative energy counter (RAPL `energy_uj`) in microjoules, [None] without
+    /// powercap support. pass two readings to [average_watts] to get the power drawn in between
+    pub energy_uj: Option<u64>,
+}
+
+/// the result of a server operation
+///
+/// ```
+/// use libcpufreq::{ServerError, ServerResult, StringError};
+///
+/// fn governor(running: bool) -> ServerResult<String, StringError> {
+///     if !running {
+///         return Err(ServerError::NotRunning);
+///     }
+///     Ok("schedutil".into())
+/// }
+///
+/// assert_eq!(governor(true).unwrap(), "schedutil");
+/// assert!(matches!(governor(false), Err(ServerError::NotRunning)));
+/// ```
+pub type ServerResult<T, E> = Result<T, ServerError<E>>;
+
+/// errors returned from a server request.
+///
+/// variants are encoded by their index, so new variants must be added at the end (after
+/// [ServerError::Other]) to keep the wire format compatible
+#[derive(Debug, Encode, Decode)]
 pub enum ServerError<E: std::error::Error> {
     /// the server is not running
     NotRunning,
@@ -45,26 +723,407 @@ pub enum ServerError<E: std::error::Error> {
     /// invalid scaling governor preset
     InvalidScalingGovernor,
     /// other error
-    Other(E)
+    Other(E),
+    /// a write succeeded but reading the value back showed the kernel did not apply it
+    WriteNotApplied,
+    /// the platform profile is not one of `platform_profile_choices`
+    InvalidPlatformProfile,
+    /// the intel_pstate status is not `active`, `passive` or `off`
+    InvalidPstateStatus,
+    /// the system does not support the request, eg the driver or file it needs is missing
+    Unsupported,
+    /// no profile with the requested name is configured
+    UnknownProfile,
+    /// the requested minimum frequency is above the requested maximum
+    InvalidLimits,
+    /// the core lists no available governors at all, eg while its driver is being switched
+    NoGovernorsAvailable,
+    /// no active stream has the [SubscriptionId], see [Request::Unsubscribe]
+    UnknownSubscription,
+}
+
+impl<E: std::error::Error> ServerError<E> {
+    /// convert the [ServerError::Other] error with `f`, leaving the other variants untouched
+    pub fn map_other<F: std::error::Error>(self, f: impl FnOnce(E) -> F) -> ServerError<F> {
+        match self {
+            Self::NotRunning => ServerError::NotRunning,
+            Self::NotRoot => ServerError::NotRoot,
+            Self::InvalidScalingGovernor => ServerError::InvalidScalingGovernor,
+            Self::WriteNotApplied => ServerError::WriteNotApplied,
+            Self::InvalidPlatformProfile => ServerError::InvalidPlatformProfile,
+            Self::InvalidPstateStatus => ServerError::InvalidPstateStatus,
+            Self::Unsupported => ServerError::Unsupported,
+            Self::UnknownProfile => ServerError::UnknownProfile,
+            Self::InvalidLimits => ServerError::InvalidLimits,
+            Self::NoGovernorsAvailable => ServerError::NoGovernorsAvailable,
+            Self::UnknownSubscription => ServerError::UnknownSubscription,
+            Self::Other(e) => ServerError::Other(f(e)),
+        }
+    }
+
+    /// whether sending the same request again may succeed. only [ServerError::NotRunning] is,
+    /// since the server may be starting up
+    pub fn retryable(&self) -> bool {
+        matches!(self, Self::NotRunning)
+    }
+
+    /// a process exit code for the error following `sysexits.h`, so that scripts can branch on
+    /// the kind of failure
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            // EX_UNAVAILABLE
+            Self::NotRunning | Self::Unsupported | Self::NoGovernorsAvailable => 69,
+            // EX_NOPERM
+            Self::NotRoot => 77,
+            // EX_DATAERR
+            Self::InvalidScalingGovernor
+            | Self::InvalidPlatformProfile
+            | Self::InvalidPstateStatus
+            | Self::UnknownProfile
+            | Self::InvalidLimits
+            | Self::UnknownSubscription => 65,
+            // EX_CANTCREAT
+            Self::WriteNotApplied => 73,
+            // EX_IOERR
+            Self::Other(_) => 74,
+        }
+    }
+
+    /// erase the concrete error type, keeping only its message. this lets clients handle errors
+    /// from any server implementation uniformly
+    pub fn to_string_error(self) -> ServerError<StringError> {
+        self.map_other(|e| StringError(e.to_string()))
+    }
+}
+
+impl<E: std::error::Error> fmt::Display for ServerError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotRunning => write!(f, "the server is not running"),
+            Self::NotRoot => write!(f, "the server is not running as root"),
+            Self::InvalidScalingGovernor => write!(f, "invalid scaling governor"),
+            Self::WriteNotApplied => write!(f, "the kernel did not apply the write"),
+            Self::InvalidPlatformProfile => write!(f, "invalid platform profile"),
+            Self::InvalidPstateStatus => write!(f, "invalid intel_pstate status"),
+            Self::Unsupported => write!(f, "not supported on this system"),
+            Self::UnknownProfile => write!(f, "unknown profile"),
+            Self::InvalidLimits => write!(f, "the minimum frequency is above the maximum"),
+            Self::NoGovernorsAvailable => write!(f, "no governors available"),
+            Self::UnknownSubscription => write!(f, "unknown subscription"),
+            Self::Other(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl<E: std::error::Error> std::error::Error for ServerError<E> {}
+
+/// an error that only carries a message, see [ServerError::to_string_error]
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct StringError(pub String);
+
+impl fmt::Display for StringError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for StringError {}
+
+/// errors from parsing user supplied values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// the input was empty
+    Empty,
+    /// the numeric part could not be parsed
+    InvalidNumber(String),
+    /// the unit suffix is not recognised
+    InvalidUnit(String),
+    /// a range whose start is after its end
+    InvalidRange(String),
+    /// a stride of 0, which would never advance
+    InvalidStride(String),
 }
 
-#[derive(Debug, Clone)]
-/// A request sent to the server
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty input"),
+            Self::InvalidNumber(number) => write!(f, "invalid number `{number}`"),
+            Self::InvalidUnit(unit) => write!(f, "unknown unit `{unit}`"),
+            Self::InvalidRange(range) => write!(f, "invalid range `{range}`"),
+            Self::InvalidStride(step) => write!(f, "invalid stride `{step}`, must be at least 1"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Encode, Decode)]
+/// A request sent to the server.
+///
+/// variants are encoded by their index, so new variants must be added at the end to keep the wire
+/// format compatible
 pub enum Request {
     /// get information at a given [CpuCores]
     Get(CpuCores),
-    /// set scaling for a [CpuCore]
+    /// set scaling for a [CpuCore]. answered with [Response::Applied] so the change can be undone
     Set(CpuCores, ScalingType),
     /// list scaling governors for [CpuCores]
     List(CpuCores),
+    /// set scaling for every core whose current governor is `current_governor`.
+    ///
+    /// matching cores are found first and then changed one at a time, so this is best-effort per
+    /// core: a failure on one core does not undo the cores that were already changed
+    SetWhere {
+        /// the governor a core must currently be using to be changed
+        current_governor: String,
+        /// the scaling to apply to the matching cores
+        new: ScalingType,
+    },
+    /// get a summary for each physical package
+    GetPackages,
+    /// get the ACPI platform profile. this is system wide and independent of the governors
+    GetPlatformProfile,
+    /// set the ACPI platform profile to one of `platform_profile_choices`
+    SetPlatformProfile(String),
+    /// dump every readable cpufreq attribute of one core, for bug reports
+    DumpCore(u8),
+    /// stream [Response::Information] for [CpuCores], sending a new frame only when a governor or
+    /// clock speed changes. the first frame is the current information
+    WatchChanges(CpuCores),
+    /// get the intel_pstate mode, fails with [ServerError::Unsupported] without intel_pstate
+    GetPstateStatus,
+    /// switch intel_pstate to `active`, `passive` or `off`. this changes which governors are
+    /// available, so clients should fetch them again afterwards
+    SetPstateStatus(String),
+    /// get what the server's system supports
+    Capabilities,
+    /// get the number of cores (one more than the highest present core), the `total` for
+    /// [CpuCores::resolve]
+    CoreCount,
+    /// turn simultaneous multithreading (hyperthreading) on or off, through the kernel's SMT
+    /// control where it has one and otherwise by offlining the secondary thread siblings of every
+    /// physical core and onlining them again. cpu0 is never offlined
+    SetSmt(bool),
+    /// like [Request::Get], with extra options. [Request::Get] is the same as passing
+    /// [GetOptions::default]
+    GetWithOptions(CpuCores, GetOptions),
+    /// restore the factory defaults of every online core: the driver's default governor, the full
+    /// hardware frequency range and the default energy performance preference, then re-enable
+    /// turbo boost. the default governor is `powersave` under `intel_pstate` and `amd-pstate-epp`
+    /// and `schedutil` (or the first available one) under every other driver
+    ResetAll,
+    /// like [Request::Get] for every core, but only the cores whose current frequency
+    /// ([PerCpuInformation::khz]) is strictly above the threshold in kHz are returned
+    GetAbove(u64),
+    /// the power drawn by each package, measured by sampling its RAPL energy counter twice over a
+    /// short window. needs powercap RAPL support (most Intel and AMD Zen systems, rarely VMs) and
+    /// root on kernels since 5.10, fails with [ServerError::Unsupported] otherwise
+    GetPower,
+    /// online or offline cores. cpu0 cannot be offlined and is skipped. offlining more than one
+    /// core is answered with [Response::ConfirmationRequired] until the request is resent with
+    /// the token from that response
+    SetOnline {
+        /// the cores to change
+        cores: CpuCores,
+        /// whether the cores should be online
+        online: bool,
+        /// the token from [Response::ConfirmationRequired], if any
+        confirmation: Option<u64>,
+    },
+    /// frequency statistics of each core. cores without statistics are left out, and if none
+    /// have any (the stats module is not loaded) this fails with [ServerError::Unsupported]
+    GetStats(CpuCores),
+    /// reset the frequency statistics of each core through `stats/reset`. cores without the file
+    /// are skipped, and if none have it this fails with [ServerError::Unsupported]
+    ResetStats(CpuCores),
+    /// keep every pending [ScalingType::WithFallback] in place for another `after`
+    Keepalive,
+    /// stream several selections on one connection, each at its own interval. every frame is a
+    /// [Response::Selection] tagged with the selection's index in the list, and each selection's
+    /// first frame is sent straight away. intervals shorter than the server's minimum poll interval
+    /// are raised to it. an empty list is answered with [Response::Ack]
+    Subscribe(Vec<(CpuCores, Duration)>),
+    /// only the governor of each core, a fast path for clients that poll governors. offline cores
+    /// are left out
+    GetGovernor(CpuCores),
+    /// apply the configured profile with this name, see [Profile]. an unknown name fails with
+    /// [ServerError::UnknownProfile]
+    ApplyProfile(String),
+    /// the configured profiles that [Request::ApplyProfile] accepts
+    ListProfiles,
+    /// stream [Response::Information] for [CpuCores] like [Request::WatchChanges], but only when a
+    /// core's clock speed moved by more than this many megahertz since the last frame sent. the
+    /// first frame is the current information
+    SubscribeDelta(CpuCores, u64),
+    /// read a tunable of the core's current governor, eg `up_threshold` for `ondemand`. fails with
+    /// [ServerError::Unsupported] if the governor has no such tunable
+    GetTunable {
+        /// the core whose governor to read
+        core: u8,
+        /// the tunable's file name
+        name: String,
+    },
+    /// write a tunable of the core's current governor, see [Request::GetTunable]. with a global
+    /// governor directory this changes the tunable for every core
+    SetTunable {
+        /// the core whose governor to change
+        core: u8,
+        /// the tunable's file name
+        name: String,
+        /// the new value
+        value: String,
+    },
+    /// the settings the server is running with, for checking what it is actually doing
+    GetConfig,
+    /// stream a [Response::ExternalChange] whenever a core's governor changes to one the server
+    /// did not apply, eg because `tuned` or TLP is also managing it. the first frame is the current
+    /// [Response::Governors]
+    WatchExternal(CpuCores),
+    /// every tunable of the core's current governor with its value, sorted by name, so clients
+    /// need not know each governor's tunables. see [Request::GetTunable]
+    ListTunables(u8),
+    /// get whether the NMI watchdog (`/proc/sys/kernel/nmi_watchdog`) is enabled. fails with
+    /// [ServerError::Unsupported] if the kernel has none
+    GetNmiWatchdog,
+    /// enable or disable the NMI watchdog. it keeps a performance counter busy and wakes the cpu,
+    /// so power tuners often disable it
+    SetNmiWatchdog(bool),
+    /// stop the stream with this id, eg from another request on the same connection. the stream
+    /// sends no more frames. fails with [ServerError::UnknownSubscription] if it already ended or
+    /// another connection started it
+    Unsubscribe(SubscriptionId),
+    /// get the present and online cores with their packages and cpufreq policies in one call,
+    /// everything needed to resolve [CpuCores::All] and ranges and to group cores in a UI
+    Topology,
+}
+
+/// identifies one stream of a connection, assigned by the server when the streaming request is
+/// accepted, see [Request::is_streaming]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Encode, Decode)]
+pub struct SubscriptionId(pub u32);
+
+impl Request {
+    /// whether the server answers the request with a stream of frames rather than a single
+    /// response. streams start with [Response::Subscribed] and every later frame is a
+    /// [Response::Frame]
+    pub fn is_streaming(&self) -> bool {
+        match self {
+            Self::WatchChanges(_) | Self::WatchExternal(_) | Self::SubscribeDelta(..) => true,
+            Self::Subscribe(selections) => !selections.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// whether the request changes any system state
+    pub fn is_mutating(&self) -> bool {
+        matches!(
+            self,
+            Self::Set(..)
+                | Self::SetWhere { .. }
+                | Self::SetPlatformProfile(_)
+                | Self::SetPstateStatus(_)
+                | Self::SetSmt(_)
+                | Self::ResetAll
+                | Self::SetOnline { .. }
+                | Self::ResetStats(_)
+                | Self::ApplyProfile(_)
+                | Self::SetTunable { .. }
+                | Self::SetNmiWatchdog(_)
+        )
+    }
+}
+
+/// options for [Request::GetWithOptions]
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct GetOptions {
+    /// also read the measured frequency into [PerCpuInformation::measured_khz].
+    /// `cpuinfo_cur_freq` is only readable by root and is slower to read than `scaling_cur_freq`
+    pub include_measured: bool,
+    /// read the current frequency (`scaling_cur_freq`) into [PerCpuInformation::khz] and
+    /// [PerCpuInformation::megahertz]. turning this off saves a read per core when only governors
+    /// are needed. defaults to true
+    pub include_frequency: bool,
+    /// include [PerCpuInformation::time_in_state], which has a pair per frequency and so makes up
+    /// most of a response's size
+    pub include_time_in_state: bool,
+    /// include [PerCpuInformation::available_frequencies], which can be long
+    pub include_available_frequencies: bool,
+    /// answer with an [Information::Uniform] when every core has the same information, see
+    /// [Information::collapse]. off by default since clients older than the uniform form cannot
+    /// decode it
+    pub collapse: bool,
 }
 
-#[derive(Debug, Clone)]
+impl Default for GetOptions {
+    fn default() -> Self {
+        Self {
+            include_measured: false,
+            include_frequency: true,
+            include_time_in_state: false,
+            include_available_frequencies: false,
+            collapse: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
 /// scaling types. may be expanded to support setting the speed in hertz instead of using a preset
 /// scaling governor
 pub enum ScalingType {
     /// preset scaling governor
     Preset(String),
+    /// move `scaling_max_freq` by a signed number of kHz. the result is clamped to the hardware
+    /// limits (`cpuinfo_min_freq` and `cpuinfo_max_freq`), so a delta past either bound leaves
+    /// the maximum at that bound rather than failing
+    AdjustMax(i64),
+    /// run the core at full speed. pstate drivers in active mode (`intel_pstate`,
+    /// `amd-pstate-epp`) are switched to the `performance` governor, which they implement in
+    /// hardware. other drivers keep their governor and get `scaling_min_freq` and
+    /// `scaling_max_freq` both set to `cpuinfo_max_freq`
+    Pin,
+    /// run the core as slowly as possible to save power without offlining it. both scaling limits
+    /// are set to `cpuinfo_min_freq`, and pstate drivers in active mode are also switched to the
+    /// `powersave` governor
+    Park,
+    /// a dead man's switch for risky settings: apply `primary` now and switch to `fallback` once
+    /// `after` has passed without a [Request::Keepalive]
+    WithFallback {
+        /// the governor to apply now
+        primary: Governor,
+        /// the governor to revert to
+        fallback: Governor,
+        /// how long `primary` lasts without a keepalive
+        after: Duration,
+    },
+    /// set `scaling_min_freq` and `scaling_max_freq` together, in whichever order the kernel
+    /// accepts. a minimum above the maximum fails with [ServerError::InvalidLimits] before
+    /// anything is written
+    MinMax {
+        /// the new `scaling_min_freq` in kHz
+        min_khz: u64,
+        /// the new `scaling_max_freq` in kHz
+        max_khz: u64,
+    },
+}
+
+impl ScalingType {
+    /// whether a server whose system has `caps` can apply this, to enable UI controls or check a
+    /// request before sending it. governors must be in [Capabilities::governors], and scaling limit
+    /// changes need a cpufreq driver. the server may still refuse, eg a frequency outside the
+    /// hardware limits
+    pub fn is_applicable(&self, caps: &Capabilities) -> bool {
+        let available = |governor: &str| caps.governors.iter().any(|g| g == governor);
+        match self {
+            Self::Preset(governor) => available(governor),
+            Self::WithFallback {
+                primary, fallback, ..
+            } => available(primary.as_str()) && available(fallback.as_str()),
+            Self::AdjustMax(_) | Self::Pin | Self::Park | Self::MinMax { .. } => {
+                caps.driver.is_some()
+            }
+        }
+    }
 }
 
 /// Type for cpu cores, can be
@@ -72,7 +1131,8 @@ pub enum ScalingType {
 /// - [CpuCores::One]
 /// - [CpuCores::Multiple]
 /// - [CpuCores::Range]
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum CpuCores {
     #[default]
     /// Apply to all CPU cores
@@ -84,3 +1144,745 @@ pub enum CpuCores {
     /// A range of CPU cores (example: CPU0-5)
     Range(u8, u8),
 }
+
+impl CpuCores {
+    /// the core indices selected, given the total number of cores
+    pub fn resolve(&self, total: u8) -> Vec<u8> {
+        match self {
+            Self::All => (0..total).collect(),
+            Self::One(core) => vec![*core],
+            Self::Multiple(cores) => cores.clone(),
+            Self::Range(start, end) => (*start..=*end).collect(),
+        }
+    }
+
+    /// the selected cores that are also in `online`, eg from the `online` cpu list, as
+    /// [CpuCores::Multiple]. [CpuCores::All] selects every online core. the selection's order is
+    /// kept and `online` does not need to be sorted
+    pub fn clamp_to_online(&self, online: &[u8]) -> CpuCores {
+        let cores = match self {
+            Self::All => {
+                let mut online = online.to_vec();
+                online.sort_unstable();
+                online.dedup();
+                online
+            }
+            cores => cores
+                .resolve(u8::MAX)
+                .into_iter()
+                .filter(|core| online.contains(core))
+                .collect(),
+        };
+        Self::Multiple(cores)
+    }
+
+    /// the selected cores in kernel cpuset syntax, eg `0-3,5`, for cgroup tooling. see
+    /// [format_cpu_list]
+    pub fn to_cpuset_string(&self, total: u8) -> String {
+        format_cpu_list(&self.resolve(total))
+    }
+
+    /// parse kernel cpuset syntax, eg `0-3,5`, into [CpuCores::Multiple], sorted and without
+    /// duplicates. an empty cpuset selects no cores. unlike [CpuCores::from_str], `all` is not
+    /// accepted
+    pub fn from_cpuset_string(s: &str) -> Result<Self, ParseError> {
+        let mut cores = parse_cpu_list(s).ok_or_else(|| ParseError::InvalidNumber(s.into()))?;
+        cores.sort_unstable();
+        cores.dedup();
+        Ok(Self::Multiple(cores))
+    }
+}
+
+/// union the per core information of several responses, eg from a series of `Get(Range)`
+/// requests, returning the first [Response::Error] instead if there is one. an
+/// [Information::Uniform] is expanded into an entry per core. a core in more than one response
+/// takes its information from the last. responses that carry no per core information (including
+/// [Information::All] and an [Information::Uniform] over [CpuCores::All]) are skipped
+pub fn merge_responses<E: std::error::Error>(
+    responses: Vec<Response<E>>,
+) -> ServerResult<Information, E> {
+    let mut merged = HashMap::new();
+    for response in responses {
+        match response {
+            Response::Error(e) => return Err(e),
+            Response::Information(
+                information @ (Information::Table(_) | Information::Uniform { .. }),
+            ) => {
+                // a uniform over all cores expands to nothing, its core count is unknown here
+                if let Information::Table(table) = information.expand(0) {
+                    merged.extend(table);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(Information::Table(merged))
+}
+
+/// parses `all`, a core (`4`), a range (`0-5`), a strided range (`0-7:2`) or a comma separated
+/// list of them (`0,2,4-6`). a strided range `start-end:step` takes every `step`th core from
+/// `start` up to and including `end`, so `0-7:2` is `0,2,4,6` and `1-7:2` is `1,3,5,7`. lists and
+/// strided ranges are expanded into [CpuCores::Multiple] without duplicates, so the result never
+/// holds more than 256 cores however long the input is
+impl FromStr for CpuCores {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(Self::All);
+        }
+
+        if !s.contains(',') && !s.contains(':') {
+            let (start, end) = parse_core_range(s)?;
+            return Ok(if s.contains('-') {
+                Self::Range(start, end)
+            } else {
+                Self::One(start)
+            });
+        }
+
+        let mut selected = [false; 256];
+        for part in s.split(',') {
+            let (range, step) = match part.split_once(':') {
+                Some((range, step)) => (range, parse_stride(step)?),
+                None => (part, 1),
+            };
+            let (start, end) = parse_core_range(range)?;
+            for core in (start..=end).step_by(step) {
+                selected[core as usize] = true;
+            }
+        }
+        Ok(Self::Multiple(
+            (0..=u8::MAX).filter(|core| selected[*core as usize]).collect(),
+        ))
+    }
+}
+
+/// `a-b` or a single core `a` (as `a-a`)
+fn parse_core_range(s: &str) -> Result<(u8, u8), ParseError> {
+    let parse = |core: &str| {
+        core.trim()
+            .parse::<u8>()
+            .map_err(|_| ParseError::InvalidNumber(core.into()))
+    };
+    match s.split_once('-') {
+        Some((start, end)) => {
+            let (start, end) = (parse(start)?, parse(end)?);
+            if start > end {
+                return Err(ParseError::InvalidRange(s.into()));
+            }
+            Ok((start, end))
+        }
+        None => parse(s).map(|core| (core, core)),
+    }
+}
+
+/// the `step` of a strided range. 0 is rejected with [ParseError::InvalidStride], steps longer
+/// than the range are fine and select only its start
+fn parse_stride(step: &str) -> Result<usize, ParseError> {
+    match step.trim().parse::<usize>() {
+        Ok(0) => Err(ParseError::InvalidStride(step.into())),
+        Ok(step) => Ok(step),
+        Err(_) => Err(ParseError::InvalidNumber(step.into())),
+    }
+}
+
+/// parse a kernel cpu list such as `0-3,5` (the format of `/sys/devices/system/cpu/present`).
+/// space separated lists like `related_cpus`' `0 1 2 3` are accepted too. returns [None] if the
+/// list is malformed
+pub fn parse_cpu_list(s: &str) -> Option<Vec<u8>> {
+    let mut cores = Vec::new();
+    for part in s
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+    {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (start.parse::<u8>().ok()?, end.parse::<u8>().ok()?);
+                if start > end {
+                    return None;
+                }
+                cores.extend(start..=end);
+            }
+            None => cores.push(part.parse().ok()?),
+        }
+    }
+    Some(cores)
+}
+
+/// format cores as a kernel cpu list such as `0-3,5`, the inverse of [parse_cpu_list]. the cores
+/// are sorted and duplicates dropped, and runs of consecutive cores become ranges
+pub fn format_cpu_list(cores: &[u8]) -> String {
+    let mut cores = cores.to_vec();
+    cores.sort_unstable();
+    cores.dedup();
+
+    let mut ranges: Vec<(u8, u8)> = Vec::new();
+    for core in cores {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == core => *end = core,
+            _ => ranges.push((core, core)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{start}-{end}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, ErrorKind};
+
+    use super::*;
+
+    mod golden;
+
+    /// an online core on `governor` running at `megahertz`
+    fn core(governor: &str, megahertz: u64) -> PerCpuInformation {
+        PerCpuInformation {
+            governor: governor.into(),
+            megahertz: Some(megahertz),
+            khz: Some(megahertz * 1000),
+            online: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn display_summarizes_a_table() {
+        let info = Information::Table(HashMap::from([
+            (0, core("schedutil", 2000)),
+            (1, core("performance", 3000)),
+            (2, core("schedutil", 2500)),
+        ]));
+        assert_eq!(
+            info.to_string(),
+            "3 cores, governors: [performance, schedutil], avg 2.5 GHz"
+        );
+    }
+
+    #[test]
+    fn to_string_error_erases_the_error_type() {
+        let message = "scaling_governor is read only";
+        let error = io::Error::new(ErrorKind::PermissionDenied, message);
+        let erased: ServerError<StringError> = ServerError::Other(error).to_string_error();
+        assert!(matches!(&erased, ServerError::Other(StringError(m)) if m == message));
+        assert_eq!(erased.to_string(), message);
+        let erased = ServerError::<io::Error>::NotRoot.to_string_error();
+        assert!(matches!(erased, ServerError::NotRoot));
+    }
+
+    #[test]
+    fn unset_per_cpu_fields_take_no_space_on_the_wire() {
+        let encode = |info: &PerCpuInformation| {
+            bincode::encode_to_vec(info, bincode::config::standard()).unwrap()
+        };
+        let decode = |bytes: &[u8]| {
+            let (info, read): (PerCpuInformation, _) =
+                bincode::decode_from_slice(bytes, bincode::config::standard()).unwrap();
+            assert_eq!(read, bytes.len());
+            info
+        };
+        // empty governor, offline, frequency reading and an empty mask
+        assert_eq!(encode(&PerCpuInformation::default()), [0, 0, 1, 0]);
+
+        let full = PerCpuInformation {
+            governor: "userspace".into(),
+            megahertz: Some(1800),
+            khz: Some(1_800_000),
+            online: true,
+            scaling_min_khz: Some(800_000),
+            scaling_max_khz: Some(3_600_000),
+            cpuinfo_min_khz: Some(400_000),
+            cpuinfo_max_khz: Some(4_200_000),
+            thread_siblings: Some(vec![0, 8]),
+            measured_khz: Some(1_799_000),
+            base_frequency_khz: Some(2_100_000),
+            time_in_state: Some(vec![(800_000, 12), (1_800_000, 34)]),
+            frequency: FrequencyReading::Known(1_800_000),
+            frequency_step_khz: Some(100_000),
+            available_frequencies: Some(vec![800_000, 1_800_000]),
+        };
+        assert!(decode(&encode(&full)) == full);
+        // each field alone, so a mix up of two bits cannot cancel out
+        let fields: [fn(&mut PerCpuInformation, &PerCpuInformation); 12] = [
+            |info, full| info.megahertz = full.megahertz,
+            |info, full| info.khz = full.khz,
+            |info, full| info.scaling_min_khz = full.scaling_min_khz,
+            |info, full| info.scaling_max_khz = full.scaling_max_khz,
+            |info, full| info.cpuinfo_min_khz = full.cpuinfo_min_khz,
+            |info, full| info.cpuinfo_max_khz = full.cpuinfo_max_khz,
+            |info, full| info.thread_siblings = full.thread_siblings.clone(),
+            |info, full| info.measured_khz = full.measured_khz,
+            |info, full| info.base_frequency_khz = full.base_frequency_khz,
+            |info, full| info.time_in_state = full.time_in_state.clone(),
+            |info, full| info.frequency_step_khz = full.frequency_step_khz,
+            |info, full| info.available_frequencies = full.available_frequencies.clone(),
+        ];
+        for set in fields {
+            let mut info = PerCpuInformation::default();
+            set(&mut info, &full);
+            assert!(decode(&encode(&info)) == info);
+        }
+
+        // a mask bit this version does not know about
+        let mut unknown = encode(&PerCpuInformation::default());
+        *unknown.last_mut().unwrap() = 251;
+        unknown.extend_from_slice(&(1u16 << 12).to_le_bytes());
+        assert!(
+            bincode::decode_from_slice::<PerCpuInformation, _>(
+                &unknown,
+                bincode::config::standard()
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn headroom_of_a_partially_boosted_table() {
+        let max = |mut info: PerCpuInformation, max_mhz: u64| {
+            info.cpuinfo_max_khz = Some(max_mhz * 1000);
+            info
+        };
+        let info = Information::Table(HashMap::from([
+            (0, max(core("performance", 4000), 4000)),
+            (1, max(core("schedutil", 2000), 4000)),
+            // no hardware maximum, so left out of both sums
+            (2, core("schedutil", 1000)),
+        ]));
+        assert_eq!(info.headroom_percent(), Some(25.0));
+        let info = Information::Table(HashMap::from([(0, core("schedutil", 1000))]));
+        assert_eq!(info.headroom_percent(), None);
+    }
+
+    #[test]
+    fn cores_by_frequency_is_fastest_first() {
+        let mut idle = core("schedutil", 0);
+        idle.megahertz = None;
+        let info = Information::Table(HashMap::from([
+            (0, core("schedutil", 2000)),
+            (1, core("performance", 3000)),
+            (2, core("schedutil", 2000)),
+            (3, idle),
+        ]));
+        assert_eq!(info.cores_by_frequency(), [(1, 3000), (0, 2000), (2, 2000)]);
+    }
+
+    #[test]
+    fn parsing_adversarial_cpu_cores_never_panics() {
+        let long_list = "0,".repeat(100_000) + "3";
+        let trailing_comma = "1,".repeat(10_000);
+        let cases: &[(&str, Result<Vec<u8>, ParseError>)] = &[
+            ("999", Err(ParseError::InvalidNumber("999".into()))),
+            ("5-2", Err(ParseError::InvalidRange("5-2".into()))),
+            ("0-255:1", Ok((0..=u8::MAX).collect())),
+            ("254-255,255", Ok(vec![254, 255])),
+            (&long_list, Ok(vec![0, 3])),
+            (&trailing_comma, Err(ParseError::InvalidNumber("".into()))),
+            ("-", Err(ParseError::InvalidNumber("".into()))),
+            (":", Err(ParseError::InvalidNumber("".into()))),
+            ("0-7:0", Err(ParseError::InvalidStride("0".into()))),
+            (
+                "0-7:99999999999999999999999",
+                Err(ParseError::InvalidNumber("99999999999999999999999".into())),
+            ),
+            ("0--1", Err(ParseError::InvalidNumber("-1".into()))),
+            ("1:2:3", Err(ParseError::InvalidNumber("2:3".into()))),
+            (",,,", Err(ParseError::InvalidNumber("".into()))),
+            ("", Err(ParseError::Empty)),
+        ];
+        for (input, expected) in cases {
+            let parsed = input.parse::<CpuCores>().map(|cores| cores.resolve(0));
+            assert_eq!(&parsed, expected, "{input:.20}");
+        }
+    }
+
+    #[test]
+    fn merge_responses_unions_tables_and_stops_at_an_error() {
+        let responses: Vec<Response<io::Error>> = vec![
+            Response::Information(Information::Table(HashMap::from([
+                (0, core("schedutil", 2000)),
+                (1, core("schedutil", 2100)),
+            ]))),
+            Response::Information(Information::Uniform {
+                cores: CpuCores::Range(2, 3),
+                info: core("powersave", 800),
+            }),
+            Response::Information(Information::Table(HashMap::from([(
+                1,
+                core("performance", 3000),
+            )]))),
+        ];
+        let merged = merge_responses(responses).unwrap();
+        let governors: Vec<(u8, &str)> = merged
+            .by_core()
+            .into_iter()
+            .map(|(core, info)| (core, info.governor.as_str()))
+            .collect();
+        assert_eq!(
+            governors,
+            [
+                (0, "schedutil"),
+                (1, "performance"),
+                (2, "powersave"),
+                (3, "powersave"),
+            ]
+        );
+
+        let responses: Vec<Response<io::Error>> = vec![
+            Response::Information(Information::Table(HashMap::from([(
+                0,
+                core("schedutil", 2000),
+            )]))),
+            Response::Error(ServerError::NotRunning),
+            Response::Error(ServerError::NotRoot),
+        ];
+        assert!(matches!(
+            merge_responses(responses),
+            Err(ServerError::NotRunning)
+        ));
+    }
+
+    #[test]
+    fn governors_by_core_is_sorted_and_skips_cores_without_one() {
+        let mut offline = core("schedutil", 1000);
+        offline.online = false;
+        let info = Information::Table(HashMap::from([
+            (3, core("schedutil", 2000)),
+            (0, core("performance", 3000)),
+            (2, offline),
+            (1, core("", 1200)),
+            (5, core("powersave", 800)),
+        ]));
+        assert_eq!(
+            info.governors_by_core(),
+            [(0, "performance"), (3, "schedutil"), (5, "powersave")]
+        );
+        let info = Information::All(core("schedutil", 2000));
+        assert_eq!(info.governors_by_core(), [(ALL_CORES, "schedutil")]);
+    }
+
+    #[test]
+    fn parse_thread_siblings_lists() {
+        assert_eq!(parse_cpu_list("0,4\n"), Some(vec![0, 4]));
+        assert_eq!(parse_cpu_list("2-3"), Some(vec![2, 3]));
+        assert_eq!(parse_cpu_list("0,4-x"), None);
+        assert_eq!(parse_cpu_list("5-4"), None);
+    }
+
+    #[test]
+    fn cpu_lists_round_trip_for_any_set_of_cores() {
+        let round_trip = |selected: &[bool; 256]| {
+            let cores: Vec<u8> = (0..=u8::MAX).filter(|core| selected[*core as usize]).collect();
+            let list = format_cpu_list(&cores);
+            assert_eq!(parse_cpu_list(&list).as_ref(), Some(&cores), "{list}");
+        };
+        // every subset of the lowest and of the highest 16 cores, which covers every pattern of
+        // runs and gaps at both ends of the range
+        for bits in 0..=u16::MAX {
+            let mut low = [false; 256];
+            let mut high = [false; 256];
+            for i in 0..16 {
+                low[i] = bits & 1 << i != 0;
+                high[240 + i] = low[i];
+            }
+            round_trip(&low);
+            round_trip(&high);
+        }
+        // and random subsets of all 256, from a fixed seed so failures reproduce
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        for density in [2, 4, 8, 16] {
+            for _ in 0..2_000 {
+                let mut selected = [false; 256];
+                for selected in &mut selected {
+                    // xorshift64
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    *selected = !state.is_multiple_of(density);
+                }
+                round_trip(&selected);
+            }
+        }
+        round_trip(&[true; 256]);
+    }
+
+    #[test]
+    fn exit_codes_follow_sysexits() {
+        let cases: [(ServerError<io::Error>, i32); 12] = [
+            (ServerError::NotRunning, 69),
+            (ServerError::Unsupported, 69),
+            (ServerError::NoGovernorsAvailable, 69),
+            (ServerError::NotRoot, 77),
+            (ServerError::InvalidScalingGovernor, 65),
+            (ServerError::InvalidPlatformProfile, 65),
+            (ServerError::InvalidPstateStatus, 65),
+            (ServerError::UnknownProfile, 65),
+            (ServerError::InvalidLimits, 65),
+            (ServerError::UnknownSubscription, 65),
+            (ServerError::WriteNotApplied, 73),
+            (ServerError::Other(ErrorKind::BrokenPipe.into()), 74),
+        ];
+        for (error, code) in cases {
+            assert_eq!(error.exit_code(), code, "{error}");
+        }
+    }
+
+    #[test]
+    fn turbo_ratio_against_the_base_frequency() {
+        let mut boosted = core("performance", 4500);
+        boosted.base_frequency_khz = Some(3_000_000);
+        assert_eq!(boosted.turbo_ratio(), Some(1.5));
+        let mut base = core("performance", 3000);
+        base.base_frequency_khz = Some(3_000_000);
+        assert_eq!(base.turbo_ratio(), Some(1.0));
+        assert_eq!(core("performance", 3000).turbo_ratio(), None);
+    }
+
+    #[test]
+    fn encoded_size_matches_the_encoding() {
+        let table = Response::<StringError>::Information(Information::Table(
+            (0..8)
+                .map(|id| (id, core("schedutil", 2000 + u64::from(id))))
+                .collect(),
+        ));
+        let ack = Response::<StringError>::Ack;
+        assert!(ack.encoded_size() < table.encoded_size());
+        for response in [ack, table] {
+            let encoded = bincode::encode_to_vec(&response, bincode::config::standard()).unwrap();
+            assert_eq!(response.encoded_size(), encoded.len());
+        }
+    }
+
+    #[test]
+    fn clamp_to_online_drops_offline_cores() {
+        let online = [3, 0, 1, 2, 5];
+        let clamped = CpuCores::Range(0, 15).clamp_to_online(&online);
+        assert!(matches!(clamped, CpuCores::Multiple(cores) if cores == [0, 1, 2, 3, 5]));
+        let clamped = CpuCores::Multiple(vec![5, 4, 1]).clamp_to_online(&online);
+        assert!(matches!(clamped, CpuCores::Multiple(cores) if cores == [5, 1]));
+        let clamped = CpuCores::All.clamp_to_online(&[1, 0, 1]);
+        assert!(matches!(clamped, CpuCores::Multiple(cores) if cores == [0, 1]));
+        let clamped = CpuCores::One(7).clamp_to_online(&online);
+        assert!(matches!(clamped, CpuCores::Multiple(cores) if cores.is_empty()));
+    }
+
+    #[test]
+    fn effective_governor_follows_the_policy_leader() {
+        let info = Information::Table(HashMap::from([
+            (0, PerCpuInformation::default()),
+            (1, core("performance", 2400)),
+            (2, core("powersave", 2400)),
+            (3, core("schedutil", 1200)),
+        ]));
+        let related = HashMap::from([(0, vec![0, 1, 2]), (1, vec![0, 1, 2]), (2, vec![0, 1, 2])]);
+        // cpu0 is offline, so cpu1 leads the domain
+        assert_eq!(info.effective_governor(2, &related), Some("performance"));
+        assert_eq!(info.effective_governor(0, &related), Some("performance"));
+        assert_eq!(info.effective_governor(3, &related), Some("schedutil"));
+        assert_eq!(info.effective_governor(7, &related), None);
+    }
+
+    #[test]
+    fn cpuset_strings_round_trip() {
+        let selections = [
+            (CpuCores::All, "0-7"),
+            (CpuCores::One(5), "5"),
+            (CpuCores::Range(2, 4), "2-4"),
+            (CpuCores::Multiple(vec![5, 0, 1, 2, 3]), "0-3,5"),
+            (CpuCores::Multiple(vec![1, 3, 7]), "1,3,7"),
+        ];
+        for (cores, cpuset) in selections {
+            assert_eq!(cores.to_cpuset_string(8), cpuset);
+            let parsed = CpuCores::from_cpuset_string(cpuset).unwrap();
+            let mut resolved = cores.resolve(8);
+            resolved.sort_unstable();
+            assert_eq!(parsed.resolve(8), resolved);
+            assert_eq!(parsed.to_cpuset_string(8), cpuset);
+        }
+        assert!(CpuCores::from_cpuset_string("all").is_err());
+    }
+
+    #[test]
+    fn csv_row_and_header() {
+        assert_eq!(
+            PerCpuInformation::csv_header(),
+            "core,governor,mhz,scaling_min_khz,scaling_max_khz"
+        );
+        let info = PerCpuInformation {
+            scaling_min_khz: Some(800_000),
+            scaling_max_khz: Some(3_000_000),
+            ..core("schedutil", 2400)
+        };
+        assert_eq!(info.to_csv_row(3), "3,schedutil,2400,800000,3000000");
+        let offline = PerCpuInformation::default();
+        assert_eq!(offline.to_csv_row(7), "7,,,,");
+    }
+
+    #[test]
+    fn scaling_types_applicable_with_and_without_support() {
+        let supported = Capabilities {
+            driver: Some("acpi-cpufreq".into()),
+            governors: vec!["performance".into(), "powersave".into()],
+            ..Default::default()
+        };
+        let bare = Capabilities::default();
+        let fallback = |primary| ScalingType::WithFallback {
+            primary,
+            fallback: Governor::Powersave,
+            after: Duration::from_secs(30),
+        };
+        let cases = [
+            (ScalingType::Preset("performance".into()), true),
+            (ScalingType::Preset("schedutil".into()), false),
+            (fallback(Governor::Performance), true),
+            (fallback(Governor::Schedutil), false),
+            (ScalingType::AdjustMax(-100_000), true),
+            (ScalingType::Pin, true),
+            (ScalingType::Park, true),
+            (
+                ScalingType::MinMax {
+                    min_khz: 800_000,
+                    max_khz: 2_000_000,
+                },
+                true,
+            ),
+        ];
+        for (scaling, applicable) in cases {
+            assert_eq!(scaling.is_applicable(&supported), applicable, "{scaling:?}");
+            assert!(!scaling.is_applicable(&bare), "{scaling:?}");
+        }
+    }
+
+    #[test]
+    fn weighted_average_with_non_uniform_weights() {
+        // two performance cores and two efficiency cores
+        let info = Information::Table(HashMap::from([
+            (0, core("schedutil", 4000)),
+            (1, core("schedutil", 4000)),
+            (2, core("schedutil", 1000)),
+            (3, core("schedutil", 1000)),
+            (4, PerCpuInformation::default()),
+        ]));
+        assert_eq!(info.weighted_average_mhz(&HashMap::new()), Some(2500.0));
+        assert_eq!(
+            info.weighted_average_mhz(&HashMap::new()),
+            info.average_mhz()
+        );
+        let weights = HashMap::from([(0, 3.0), (1, 3.0), (2, 1.0), (3, 1.0)]);
+        assert_eq!(info.weighted_average_mhz(&weights), Some(3250.0));
+        let weights = HashMap::from([(0, 0.0), (1, 0.0), (2, 0.0), (3, 0.0)]);
+        assert_eq!(info.weighted_average_mhz(&weights), None);
+    }
+
+    #[test]
+    fn uniform_information_round_trips() {
+        let table = HashMap::from([0, 1, 2, 3].map(|id| (id, core("schedutil", 2400))));
+        let collapsed = Information::Table(table.clone()).collapse();
+        assert!(matches!(
+            &collapsed,
+            Information::Uniform { cores, info }
+                if cores.resolve(0) == [0, 1, 2, 3] && *info == core("schedutil", 2400)
+        ));
+
+        let config = bincode::config::standard();
+        let bytes = bincode::encode_to_vec(&collapsed, config).unwrap();
+        let full = bincode::encode_to_vec(Information::Table(table.clone()), config).unwrap();
+        assert!(bytes.len() < full.len());
+        let (decoded, _): (Information, _) = bincode::decode_from_slice(&bytes, config).unwrap();
+        let Information::Table(expanded) = decoded.expand(4) else {
+            panic!("not a table");
+        };
+        assert!(expanded == table);
+
+        let gaps = HashMap::from([1, 3].map(|id| (id, core("schedutil", 2400))));
+        assert!(matches!(
+            Information::Table(gaps).collapse(),
+            Information::Uniform { cores: CpuCores::Multiple(cores), .. } if cores == [1, 3]
+        ));
+        let mixed = HashMap::from([(0, core("schedutil", 2400)), (1, core("powersave", 800))]);
+        assert!(matches!(
+            Information::Table(mixed).collapse(),
+            Information::Table(_)
+        ));
+    }
+
+    #[test]
+    fn all_pinned_with_pinned_and_unpinned_cores() {
+        let limited = |min_khz, max_khz| PerCpuInformation {
+            scaling_min_khz: Some(min_khz),
+            scaling_max_khz: Some(max_khz),
+            ..core("performance", 2400)
+        };
+        let pinned = Information::Table(HashMap::from([
+            (0, limited(2_400_000, 2_400_000)),
+            (1, limited(1_600_000, 1_600_000)),
+            (2, PerCpuInformation::default()),
+        ]));
+        assert!(pinned.all_pinned());
+        let unpinned = Information::Table(HashMap::from([
+            (0, limited(2_400_000, 2_400_000)),
+            (1, limited(800_000, 3_000_000)),
+        ]));
+        assert!(!unpinned.all_pinned());
+        let unknown = Information::Table(HashMap::from([(0, core("performance", 2400))]));
+        assert!(!unknown.all_pinned());
+        assert!(!Information::Table(HashMap::new()).all_pinned());
+    }
+
+    #[test]
+    fn parse_strided_ranges() {
+        let parse = |s: &str| s.parse::<CpuCores>().map(|cores| cores.resolve(0));
+        assert_eq!(parse("0-7:2"), Ok(vec![0, 2, 4, 6]));
+        assert_eq!(parse("1-7:2"), Ok(vec![1, 3, 5, 7]));
+        assert_eq!(parse("0-9:3"), Ok(vec![0, 3, 6, 9]));
+        assert_eq!(parse("0-3:2,8-11:2"), Ok(vec![0, 2, 8, 10]));
+        assert_eq!(parse("4-4:2"), Ok(vec![4]));
+    }
+
+    #[test]
+    fn strides_are_validated_and_bounded() {
+        let parse = |s: &str| s.parse::<CpuCores>().map(|cores| cores.resolve(0));
+        assert_eq!(parse("0-7:0"), Err(ParseError::InvalidStride("0".into())));
+        assert_eq!(parse("0-7: 0"), Err(ParseError::InvalidStride(" 0".into())));
+        assert_eq!(parse("7-0:2"), Err(ParseError::InvalidRange("7-0".into())));
+        // a stride longer than the range only selects its start
+        assert_eq!(parse("3-200:1000"), Ok(vec![3]));
+        assert_eq!(parse("0-255:128"), Ok(vec![0, 128]));
+        // the largest selection is every core once, however the input repeats it
+        let repeated = vec!["0-255:1"; 1000].join(",");
+        assert_eq!(parse(&repeated).map(|cores| cores.len()), Ok(256));
+    }
+
+    #[test]
+    fn config_hash_ignores_the_clock_speed() {
+        let table = |governor: &str, mhz| {
+            Information::Table(HashMap::from([
+                (0, core("schedutil", 2400)),
+                (1, core(governor, mhz)),
+            ]))
+        };
+        let hash = table("schedutil", 2400).config_hash(Some(true));
+        assert_eq!(table("schedutil", 800).config_hash(Some(true)), hash);
+        assert_ne!(table("performance", 2400).config_hash(Some(true)), hash);
+        assert_ne!(table("schedutil", 2400).config_hash(Some(false)), hash);
+        assert_ne!(table("schedutil", 2400).config_hash(None), hash);
+        // the same configuration collapsed hashes the same
+        assert_eq!(
+            table("schedutil", 2400).collapse().config_hash(Some(true)),
+            hash
+        );
+    }
+}
@@ -0,0 +1,57 @@
+//! advice for keeping the ACPI platform profile and the governors consistent
+
+use crate::Governor;
+
+/// the governor and energy performance preference that match a platform profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileRecommendation {
+    /// the recommended scaling governor
+    pub governor: Governor,
+    /// the recommended `energy_performance_preference`, eg `balance_power`
+    pub epp: &'static str,
+}
+
+/// the governor and EPP that match the platform profile `profile` (as listed in
+/// `platform_profile_choices`), or [None] for a profile this crate does not know.
+///
+/// this is only advice; nothing is applied
+pub fn recommend_for_profile(profile: &str) -> Option<ProfileRecommendation> {
+    let (governor, epp) = match profile {
+        "low-power" | "quiet" => (Governor::Powersave, "power"),
+        "cool" => (Governor::Powersave, "balance_power"),
+        "balanced" => (Governor::Schedutil, "balance_power"),
+        "balanced-performance" => (Governor::Schedutil, "balance_performance"),
+        "performance" => (Governor::Performance, "performance"),
+        _ => return None,
+    };
+    Some(ProfileRecommendation { governor, epp })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recommendation_for_each_profile() {
+        let cases = [
+            ("low-power", Governor::Powersave, "power"),
+            ("quiet", Governor::Powersave, "power"),
+            ("cool", Governor::Powersave, "balance_power"),
+            ("balanced", Governor::Schedutil, "balance_power"),
+            (
+                "balanced-performance",
+                Governor::Schedutil,
+                "balance_performance",
+            ),
+            ("performance", Governor::Performance, "performance"),
+        ];
+        for (profile, governor, epp) in cases {
+            assert_eq!(
+                recommend_for_profile(profile),
+                Some(ProfileRecommendation { governor, epp }),
+                "{profile}"
+            );
+        }
+        assert_eq!(recommend_for_profile("custom"), None);
+    }
+}
@@ -0,0 +1,127 @@
+//! power estimates from energy counters, and governors for a power budget
+
+use crate::{Capabilities, Governor};
+
+/// budgets below this many watts prefer [Governor::Powersave], about the sustained power of a thin
+/// and light laptop
+pub const LOW_POWER_BUDGET_W: f64 = 15.0;
+
+/// budgets of at least this many watts prefer [Governor::Performance], about the sustained power of
+/// a performance laptop or a small desktop
+pub const HIGH_POWER_BUDGET_W: f64 = 45.0;
+
+/// the governors advised for a budget of at least [HIGH_POWER_BUDGET_W], most preferred first
+const HIGH_BUDGET_GOVERNORS: &[&str] = &[
+    "performance",
+    "schedutil",
+    "ondemand",
+    "conservative",
+    "powersave",
+];
+/// the governors advised for a budget between the two thresholds, most preferred first
+const MEDIUM_BUDGET_GOVERNORS: &[&str] = &[
+    "schedutil",
+    "ondemand",
+    "conservative",
+    "powersave",
+    "performance",
+];
+/// the governors advised for a budget below [LOW_POWER_BUDGET_W], most preferred first
+const LOW_BUDGET_GOVERNORS: &[&str] = &[
+    "powersave",
+    "conservative",
+    "schedutil",
+    "ondemand",
+    "performance",
+];
+
+/// the average power in watts between two readings of a RAPL `energy_uj` counter taken
+/// `elapsed_ms` apart. [None] if no time passed or the counter went backwards, which happens when
+/// it wraps around at `max_energy_range_uj`
+pub fn average_watts(start_uj: u64, end_uj: u64, elapsed_ms: u64) -> Option<f64> {
+    if elapsed_ms == 0 {
+        return None;
+    }
+    let joules = end_uj.checked_sub(start_uj)? as f64 / 1_000_000.0;
+    Some(joules / (elapsed_ms as f64 / 1000.0))
+}
+
+/// advice for which governor keeps a system near a package power budget of `watts`. tight budgets,
+/// below [LOW_POWER_BUDGET_W], prefer [Governor::Powersave] and generous ones, from
+/// [HIGH_POWER_BUDGET_W], prefer [Governor::Performance]. budgets in between prefer the first of
+/// `schedutil`, `ondemand` and `conservative`, then [Governor::Powersave], as under pstate drivers
+/// whose `powersave` already scales with load. each tier falls back to the governors of the other
+/// tiers that are closest to it.
+///
+/// only governors in `caps.governors` are advised. if it is empty only `performance` and
+/// `powersave` are assumed, since every driver has those. [None] if no governor in the tier's
+/// preference list is available, eg when the system only lists `userspace`.
+///
+/// this is only advice; nothing is applied
+pub fn governor_for_power_budget(watts: f64, caps: &Capabilities) -> Option<Governor> {
+    let preference = if watts >= HIGH_POWER_BUDGET_W {
+        HIGH_BUDGET_GOVERNORS
+    } else if watts >= LOW_POWER_BUDGET_W {
+        MEDIUM_BUDGET_GOVERNORS
+    } else {
+        LOW_BUDGET_GOVERNORS
+    };
+    let available = |name: &&str| {
+        if caps.governors.is_empty() {
+            matches!(*name, "performance" | "powersave")
+        } else {
+            caps.governors.iter().any(|governor| governor == name)
+        }
+    };
+    preference
+        .iter()
+        .copied()
+        .find(available)
+        .map(Governor::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn average_watts_between_two_snapshots() {
+        // 3 J over 200 ms
+        assert_eq!(average_watts(10_000_000, 13_000_000, 200), Some(15.0));
+        assert_eq!(average_watts(10_000_000, 13_000_000, 0), None);
+        // wrapped around
+        assert_eq!(average_watts(13_000_000, 1_000, 200), None);
+    }
+
+    #[test]
+    fn governor_for_low_and_high_budgets() {
+        let caps = Capabilities {
+            governors: vec!["performance".into(), "schedutil".into(), "powersave".into()],
+            ..Default::default()
+        };
+        assert_eq!(
+            governor_for_power_budget(8.0, &caps),
+            Some(Governor::Powersave)
+        );
+        assert_eq!(
+            governor_for_power_budget(25.0, &caps),
+            Some(Governor::Schedutil)
+        );
+        assert_eq!(
+            governor_for_power_budget(65.0, &caps),
+            Some(Governor::Performance)
+        );
+
+        // without schedutil the middle tier falls back to powersave
+        let caps = Capabilities::default();
+        assert_eq!(
+            governor_for_power_budget(25.0, &caps),
+            Some(Governor::Powersave)
+        );
+        let caps = Capabilities {
+            governors: vec!["userspace".into()],
+            ..Default::default()
+        };
+        assert_eq!(governor_for_power_budget(65.0, &caps), None);
+    }
+}
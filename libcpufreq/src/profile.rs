@@ -0,0 +1,118 @@
+//! named scaling profiles, eg `gaming` or `battery`
+
+use bincode::{Decode, Encode};
+
+use crate::{Capabilities, Information};
+
+/// a named combination of settings applied together with
+/// [Request::ApplyProfile](crate::Request::ApplyProfile). settings that are [None] are left as they
+/// are
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Profile {
+    /// the scaling governor of every online core
+    pub governor: Option<String>,
+    /// whether turbo boost is enabled
+    pub boost: Option<bool>,
+    /// the `energy_performance_preference` (EPP) of every online core that has one, eg
+    /// `balance_power`
+    pub epp: Option<String>,
+}
+
+impl Profile {
+    /// a one line summary of the settings, eg `governor performance, boost on, epp performance`,
+    /// for listing profiles in a UI. a profile that changes nothing is `no changes`
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(governor) = &self.governor {
+            parts.push(format!("governor {governor}"));
+        }
+        if let Some(boost) = self.boost {
+            parts.push(format!("boost {}", if boost { "on" } else { "off" }));
+        }
+        if let Some(epp) = &self.epp {
+            parts.push(format!("epp {epp}"));
+        }
+        if parts.is_empty() {
+            "no changes".into()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+/// the name of the first of `profiles` whose settings the system is currently in, eg to show
+/// "you're on the gaming profile". the governor must be the one of every online core and boost
+/// must match [Capabilities::boost_enabled]. the EPP is not part of [Information], so it is not
+/// compared, and profiles that set neither governor nor boost never match
+pub fn detect_active_profile(
+    info: &Information,
+    caps: &Capabilities,
+    profiles: &[(String, Profile)],
+) -> Option<String> {
+    let governors = info.governors();
+    profiles
+        .iter()
+        .find(|(_, profile)| {
+            if profile.governor.is_none() && profile.boost.is_none() {
+                return false;
+            }
+            let governor = profile
+                .governor
+                .as_ref()
+                .is_none_or(|governor| governors == [governor.as_str()]);
+            let boost = profile
+                .boost
+                .is_none_or(|boost| caps.boost_enabled == Some(boost));
+            governor && boost
+        })
+        .map(|(name, _)| name.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::PerCpuInformation;
+
+    fn profile(governor: &str, boost: bool) -> Profile {
+        Profile {
+            governor: Some(governor.into()),
+            boost: Some(boost),
+            epp: None,
+        }
+    }
+
+    #[test]
+    fn detect_the_profile_the_system_is_in() {
+        let online = |governor: &str| PerCpuInformation {
+            governor: governor.into(),
+            online: true,
+            ..Default::default()
+        };
+        let info = Information::Table(HashMap::from([
+            (0, online("performance")),
+            (1, online("performance")),
+            (2, PerCpuInformation::default()),
+        ]));
+        let profiles = [
+            ("battery".to_string(), profile("powersave", false)),
+            ("gaming".to_string(), profile("performance", true)),
+            ("quiet".to_string(), profile("performance", false)),
+        ];
+        let caps = |boost_enabled| Capabilities {
+            boost_enabled,
+            ..Default::default()
+        };
+        assert_eq!(
+            detect_active_profile(&info, &caps(Some(true)), &profiles).as_deref(),
+            Some("gaming")
+        );
+        assert_eq!(
+            detect_active_profile(&info, &caps(Some(false)), &profiles).as_deref(),
+            Some("quiet")
+        );
+        assert_eq!(detect_active_profile(&info, &caps(None), &profiles), None);
+    }
+}
@@ -0,0 +1,95 @@
+//! human readable reports
+
+use std::fmt::Write;
+
+use crate::{ALL_CORES, Capabilities, Information};
+
+/// a multi section status report for `cpufreq status`: the driver and what it supports, then a
+/// table of every core, then the one line summary from [Information]'s `Display`. sections are
+/// separated by a blank line and values that are unknown are shown as `-`
+pub fn format_status(info: &Information, caps: &Capabilities) -> String {
+    let mut report = String::new();
+    let boost = match (caps.boost_enabled, caps.features.boost) {
+        (Some(true), _) => "enabled",
+        (Some(false), _) => "disabled",
+        (None, true) => "supported",
+        (None, false) => "unsupported",
+    };
+
+    // writing to a String never fails
+    let _ = writeln!(report, "driver: {}", caps.driver.as_deref().unwrap_or("-"));
+    let _ = writeln!(report, "boost: {boost}");
+    let _ = writeln!(report, "governors available: {}", caps.governors.join(" "));
+    report.push('\n');
+
+    let cores = info.by_core();
+
+    let mhz = |khz: Option<u64>| khz.map_or("-".into(), |khz| (khz / 1000).to_string());
+    let _ = writeln!(
+        report,
+        "{:<5} {:<12} {:>6} {:>8} {:>8}",
+        "core", "governor", "MHz", "min MHz", "max MHz"
+    );
+    for (core, info) in cores {
+        let core = match core {
+            ALL_CORES => "all".into(),
+            core => core.to_string(),
+        };
+        if !info.online {
+            let _ = writeln!(report, "{core:<5} offline");
+            continue;
+        }
+        let _ = writeln!(
+            report,
+            "{core:<5} {:<12} {:>6} {:>8} {:>8}",
+            info.governor,
+            info.megahertz.map_or("-".into(), |mhz| mhz.to_string()),
+            mhz(info.scaling_min_khz),
+            mhz(info.scaling_max_khz),
+        );
+    }
+    report.push('\n');
+
+    let _ = writeln!(report, "{info}");
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::PerCpuInformation;
+
+    #[test]
+    fn status_report_snapshot() {
+        let online = PerCpuInformation {
+            governor: "schedutil".into(),
+            megahertz: Some(2400),
+            khz: Some(2_400_000),
+            online: true,
+            scaling_min_khz: Some(800_000),
+            scaling_max_khz: Some(3_600_000),
+            ..Default::default()
+        };
+        let info = Information::Table(HashMap::from([(0, online), (1, Default::default())]));
+        let caps = Capabilities {
+            driver: Some("acpi-cpufreq".into()),
+            governors: vec!["performance".into(), "schedutil".into()],
+            boost_enabled: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_status(&info, &caps),
+            "driver: acpi-cpufreq\n\
+             boost: enabled\n\
+             governors available: performance schedutil\n\
+             \n\
+             core  governor        MHz  min MHz  max MHz\n\
+             0     schedutil      2400      800     3600\n\
+             1     offline\n\
+             \n\
+             2 cores, governors: [schedutil], avg 2.4 GHz\n"
+        );
+    }
+}
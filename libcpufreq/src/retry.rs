@@ -0,0 +1,123 @@
+//! retrying requests with backoff
+
+use std::io::{self, ErrorKind};
+use std::time::Duration;
+
+use crate::{Clock, Response};
+
+/// how [send_with_retry] retries
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// the most times to send, including the first attempt
+    pub max_attempts: u32,
+    /// the wait before the first retry, doubled for each one after it
+    pub initial_backoff: Duration,
+    /// the longest wait between two attempts
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+/// call `send` until it succeeds, retrying with exponential backoff on transient I/O errors and
+/// on [ServerError::retryable](crate::ServerError::retryable) errors. the last result is
+/// returned once `policy` runs out of attempts
+pub fn send_with_retry<E: std::error::Error, C: Clock>(
+    clock: &C,
+    policy: &RetryPolicy,
+    mut send: impl FnMut() -> io::Result<Response<E>>,
+) -> io::Result<Response<E>> {
+    let mut backoff = policy.initial_backoff;
+    let mut attempt = 1;
+    loop {
+        let result = send();
+        let retry = match &result {
+            Ok(Response::Error(e)) => e.retryable(),
+            Ok(_) => false,
+            Err(e) => is_transient(e),
+        };
+        if !retry || attempt >= policy.max_attempts {
+            return result;
+        }
+        clock.sleep(backoff);
+        backoff = backoff.saturating_mul(2).min(policy.max_backoff);
+        attempt += 1;
+    }
+}
+
+/// errors that happen while the server is starting or restarting
+fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::NotFound
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe
+            | ErrorKind::TimedOut
+            | ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::ServerError;
+
+    /// a [Clock] that records the sleeps instead of sleeping
+    #[derive(Default)]
+    struct MockClock(RefCell<Vec<Duration>>);
+
+    impl Clock for MockClock {
+        fn now_ms(&self) -> u64 {
+            0
+        }
+
+        fn monotonic_ms(&self) -> u64 {
+            0
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.0.borrow_mut().push(duration);
+        }
+    }
+
+    #[test]
+    fn retries_a_retryable_error() {
+        let clock = MockClock::default();
+        let mut responses = vec![Response::Ack, Response::Error(ServerError::NotRunning)];
+        let response = send_with_retry::<io::Error, _>(&clock, &RetryPolicy::default(), || {
+            Ok(responses.pop().unwrap())
+        });
+        assert!(matches!(response, Ok(Response::Ack)));
+        assert_eq!(*clock.0.borrow(), [Duration::from_millis(100)]);
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts() {
+        let clock = MockClock::default();
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            ..Default::default()
+        };
+        let mut attempts = 0;
+        let response = send_with_retry::<io::Error, _>(&clock, &policy, || {
+            attempts += 1;
+            Err(ErrorKind::ConnectionRefused.into())
+        });
+        assert!(response.is_err());
+        assert_eq!(attempts, 3);
+        let backoff = [Duration::from_millis(100), Duration::from_millis(200)];
+        assert_eq!(*clock.0.borrow(), backoff);
+    }
+}
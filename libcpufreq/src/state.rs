@@ -0,0 +1,384 @@
+//! saved CPU configurations
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use bincode::{Decode, Encode};
+
+use crate::{CpuCores, Information};
+
+/// a snapshot of the scaling configuration of a set of cores, eg to restore later
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+pub struct CpuState {
+    /// the configuration of each core
+    pub cores: HashMap<u8, CoreState>,
+    /// whether turbo boost was enabled, [None] if unknown
+    pub boost: Option<bool>,
+}
+
+/// the scaling configuration of one core
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+pub struct CoreState {
+    /// the scaling governor
+    pub governor: String,
+    /// `scaling_min_freq` in kHz
+    pub scaling_min_khz: Option<u64>,
+    /// `scaling_max_freq` in kHz
+    pub scaling_max_khz: Option<u64>,
+}
+
+/// a reason a [CpuState] cannot be restored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestoreError {
+    /// the core does not exist on this system
+    MissingCore(u8),
+    /// the governor is not available on the core
+    UnavailableGovernor {
+        /// the core
+        core: u8,
+        /// the governor that is not available
+        governor: String,
+    },
+    /// the saved minimum frequency is above the saved maximum
+    MinAboveMax(u8),
+}
+
+/// how a core's governor differs from a saved [CpuState], see [drift]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GovernorDrift {
+    /// the core runs a different governor than the saved one
+    Changed {
+        /// the saved governor
+        saved: String,
+        /// the governor in use now
+        current: String,
+    },
+    /// the core was saved but is offline or missing from the current information
+    Missing {
+        /// the saved governor
+        saved: String,
+    },
+}
+
+/// one difference between two [CpuState]s, see [CpuState::diff]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoreStateChange {
+    /// the core's governor changes
+    Governor {
+        /// the core
+        core: u8,
+        /// the governor before
+        from: String,
+        /// the governor after
+        to: String,
+    },
+    /// the core's `scaling_min_freq` changes, in kHz
+    MinKhz {
+        /// the core
+        core: u8,
+        /// the minimum before
+        from: Option<u64>,
+        /// the minimum after
+        to: Option<u64>,
+    },
+    /// the core's `scaling_max_freq` changes, in kHz
+    MaxKhz {
+        /// the core
+        core: u8,
+        /// the maximum before
+        from: Option<u64>,
+        /// the maximum after
+        to: Option<u64>,
+    },
+    /// the core is only in the later state
+    Added(u8),
+    /// the core is only in the earlier state
+    Removed(u8),
+    /// turbo boost changes. it is system wide, so this is not tied to a core
+    Boost {
+        /// whether boost was enabled before
+        from: Option<bool>,
+        /// whether boost is enabled after
+        to: Option<bool>,
+    },
+}
+
+/// the cores of `saved` whose governor no longer matches `current`, sorted by core. this detects
+/// changes made behind the daemon's back. [Information::All] is compared against every saved core
+pub fn drift(saved: &CpuState, current: &Information) -> Vec<(u8, GovernorDrift)> {
+    let mut cores: Vec<_> = saved.cores.iter().collect();
+    cores.sort_unstable_by_key(|(core, _)| **core);
+
+    cores
+        .into_iter()
+        .filter_map(|(&core, state)| {
+            let info = match current {
+                Information::All(info)
+                | Information::Uniform {
+                    cores: CpuCores::All,
+                    info,
+                } => Some(info),
+                Information::Table(table) => table.get(&core),
+                Information::Uniform { cores, info } => {
+                    cores.resolve(0).contains(&core).then_some(info)
+                }
+            };
+            let saved = state.governor.clone();
+            let drift = match info.filter(|info| info.online) {
+                None => GovernorDrift::Missing { saved },
+                Some(info) if info.governor != saved => GovernorDrift::Changed {
+                    saved,
+                    current: info.governor.clone(),
+                },
+                Some(_) => return None,
+            };
+            Some((core, drift))
+        })
+        .collect()
+}
+
+impl CpuState {
+    /// check that the state can be restored on a system whose cores and their available governors
+    /// are `available_governors`, without applying anything. every problem is reported, sorted by
+    /// core
+    pub fn validate(
+        &self,
+        available_governors: &HashMap<u8, Vec<String>>,
+    ) -> Result<(), Vec<RestoreError>> {
+        let mut cores: Vec<_> = self.cores.iter().collect();
+        cores.sort_unstable_by_key(|(core, _)| **core);
+
+        let mut errors = Vec::new();
+        for (&core, state) in cores {
+            let Some(available) = available_governors.get(&core) else {
+                errors.push(RestoreError::MissingCore(core));
+                continue;
+            };
+            if !available.contains(&state.governor) {
+                errors.push(RestoreError::UnavailableGovernor {
+                    core,
+                    governor: state.governor.clone(),
+                });
+            }
+            if let (Some(min), Some(max)) = (state.scaling_min_khz, state.scaling_max_khz)
+                && min > max
+            {
+                errors.push(RestoreError::MinAboveMax(core));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// what changes going from this state to `other`, eg from the current state to a saved one to
+    /// preview a restore. changes are sorted by core, a boost change comes last
+    pub fn diff(&self, other: &CpuState) -> Vec<CoreStateChange> {
+        let mut cores: Vec<u8> = self
+            .cores
+            .keys()
+            .chain(other.cores.keys())
+            .copied()
+            .collect();
+        cores.sort_unstable();
+        cores.dedup();
+
+        let mut changes = Vec::new();
+        for core in cores {
+            let (from, to) = match (self.cores.get(&core), other.cores.get(&core)) {
+                (Some(from), Some(to)) => (from, to),
+                (None, _) => {
+                    changes.push(CoreStateChange::Added(core));
+                    continue;
+                }
+                (_, None) => {
+                    changes.push(CoreStateChange::Removed(core));
+                    continue;
+                }
+            };
+            if from.governor != to.governor {
+                changes.push(CoreStateChange::Governor {
+                    core,
+                    from: from.governor.clone(),
+                    to: to.governor.clone(),
+                });
+            }
+            if from.scaling_min_khz != to.scaling_min_khz {
+                changes.push(CoreStateChange::MinKhz {
+                    core,
+                    from: from.scaling_min_khz,
+                    to: to.scaling_min_khz,
+                });
+            }
+            if from.scaling_max_khz != to.scaling_max_khz {
+                changes.push(CoreStateChange::MaxKhz {
+                    core,
+                    from: from.scaling_max_khz,
+                    to: to.scaling_max_khz,
+                });
+            }
+        }
+        if self.boost != other.boost {
+            changes.push(CoreStateChange::Boost {
+                from: self.boost,
+                to: other.boost,
+            });
+        }
+        changes
+    }
+
+    /// a shell script that applies the state with `echo` to each core's sysfs files, for use in
+    /// init scripts or by hand. the boost state is not included since its file depends on the
+    /// driver
+    pub fn to_shell_script(&self) -> String {
+        let mut cores: Vec<_> = self.cores.iter().collect();
+        cores.sort_unstable_by_key(|(core, _)| **core);
+
+        let mut script = String::from("#!/bin/sh\n");
+        for (core, state) in cores {
+            let dir = format!("/sys/devices/system/cpu/cpu{core}/cpufreq");
+            let _ = writeln!(script, "echo {} > {dir}/scaling_governor", state.governor);
+            if let Some(min) = state.scaling_min_khz {
+                let _ = writeln!(script, "echo {min} > {dir}/scaling_min_freq");
+            }
+            if let Some(max) = state.scaling_max_khz {
+                let _ = writeln!(script, "echo {max} > {dir}/scaling_max_freq");
+            }
+        }
+        script
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PerCpuInformation;
+
+    /// a [CpuState] with `cores` given as `(core, governor)` and no frequency limits
+    fn state(cores: &[(u8, &str)]) -> CpuState {
+        CpuState {
+            cores: cores
+                .iter()
+                .map(|(core, governor)| {
+                    let governor = governor.to_string();
+                    let state = CoreState {
+                        governor,
+                        ..Default::default()
+                    };
+                    (*core, state)
+                })
+                .collect(),
+            boost: None,
+        }
+    }
+
+    #[test]
+    fn validate_reports_a_missing_core() {
+        let available = HashMap::from([
+            (0, vec!["performance".into(), "powersave".into()]),
+            (1, vec!["performance".into(), "powersave".into()]),
+        ]);
+        let mut saved = state(&[(0, "powersave"), (1, "schedutil"), (4, "performance")]);
+        assert_eq!(
+            saved.validate(&available),
+            Err(vec![
+                RestoreError::UnavailableGovernor {
+                    core: 1,
+                    governor: "schedutil".into(),
+                },
+                RestoreError::MissingCore(4),
+            ])
+        );
+        saved.cores.retain(|core, _| *core == 0);
+        assert_eq!(saved.validate(&available), Ok(()));
+    }
+
+    #[test]
+    fn shell_script_echoes_each_core() {
+        let mut saved = state(&[(1, "powersave"), (0, "performance")]);
+        saved.cores.get_mut(&1).unwrap().scaling_max_khz = Some(2_000_000);
+        assert_eq!(
+            saved.to_shell_script(),
+            "#!/bin/sh\n\
+             echo performance > /sys/devices/system/cpu/cpu0/cpufreq/scaling_governor\n\
+             echo powersave > /sys/devices/system/cpu/cpu1/cpufreq/scaling_governor\n\
+             echo 2000000 > /sys/devices/system/cpu/cpu1/cpufreq/scaling_max_freq\n"
+        );
+    }
+
+    #[test]
+    fn drift_from_a_saved_baseline() {
+        let saved = state(&[(0, "schedutil"), (1, "schedutil"), (2, "powersave")]);
+        let core = |governor: &str, online| PerCpuInformation {
+            governor: governor.into(),
+            online,
+            ..Default::default()
+        };
+        let current = Information::Table(HashMap::from([
+            (0, core("schedutil", true)),
+            (1, core("performance", true)),
+            (2, core("powersave", false)),
+        ]));
+        assert_eq!(
+            drift(&saved, &current),
+            [
+                (
+                    1,
+                    GovernorDrift::Changed {
+                        saved: "schedutil".into(),
+                        current: "performance".into(),
+                    }
+                ),
+                (
+                    2,
+                    GovernorDrift::Missing {
+                        saved: "powersave".into(),
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_reports_governor_limit_and_boost_changes() {
+        let mut current = state(&[(0, "powersave"), (1, "performance"), (2, "powersave")]);
+        current.boost = Some(false);
+        let mut saved = state(&[(0, "performance"), (1, "performance"), (3, "schedutil")]);
+        saved.boost = Some(true);
+        let core1 = saved.cores.get_mut(&1).unwrap();
+        core1.scaling_min_khz = Some(800_000);
+        core1.scaling_max_khz = Some(2_000_000);
+        current.cores.get_mut(&1).unwrap().scaling_max_khz = Some(3_000_000);
+
+        assert_eq!(
+            current.diff(&saved),
+            [
+                CoreStateChange::Governor {
+                    core: 0,
+                    from: "powersave".into(),
+                    to: "performance".into(),
+                },
+                CoreStateChange::MinKhz {
+                    core: 1,
+                    from: None,
+                    to: Some(800_000),
+                },
+                CoreStateChange::MaxKhz {
+                    core: 1,
+                    from: Some(3_000_000),
+                    to: Some(2_000_000),
+                },
+                CoreStateChange::Removed(2),
+                CoreStateChange::Added(3),
+                CoreStateChange::Boost {
+                    from: Some(false),
+                    to: Some(true),
+                },
+            ]
+        );
+        assert!(saved.diff(&saved).is_empty());
+    }
+}
@@ -0,0 +1,44 @@
+//! cpufreq statistics (`cpufreq/stats`)
+
+use bincode::{Decode, Encode};
+
+/// the statistics of one core
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+pub struct CoreStats {
+    /// the number of frequency transitions (`stats/total_trans`). a count that climbs quickly
+    /// means the governor is thrashing between frequencies
+    pub total_trans: u64,
+    /// see [parse_time_in_state], empty if unreadable
+    pub time_in_state: Vec<(u64, u64)>,
+}
+
+/// parse `stats/time_in_state`: one `<frequency in kHz> <time>` pair per line, where the time is
+/// in 10ms units (USER_HZ). [None] if any line is malformed
+pub fn parse_time_in_state(s: &str) -> Option<Vec<(u64, u64)>> {
+    s.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut columns = line.split_whitespace();
+            let pair = (columns.next()?.parse().ok()?, columns.next()?.parse().ok()?);
+            columns.next().is_none().then_some(pair)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_two_column_time_in_state() {
+        let stats = "3600000 120\n2400000 4500\n800000 98211\n";
+        assert_eq!(
+            parse_time_in_state(stats),
+            Some(vec![(3_600_000, 120), (2_400_000, 4500), (800_000, 98211)])
+        );
+        assert_eq!(parse_time_in_state(""), Some(vec![]));
+        assert_eq!(parse_time_in_state("3600000\n"), None);
+        assert_eq!(parse_time_in_state("3600000 120 7\n"), None);
+        assert_eq!(parse_time_in_state("3.6GHz 120\n"), None);
+    }
+}
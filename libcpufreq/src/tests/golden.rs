@@ -0,0 +1,370 @@
+//! golden encodings of every [Request] and [Response] variant.
+//!
+//! variants are encoded by their index, so reordering them or changing a field type silently
+//! breaks clients built against an older version. a failure here means the wire format changed:
+//! if that was intended, every client and server must be upgraded together and the bytes below
+//! updated to match
+
+use std::time::Duration;
+
+use super::*;
+
+/// `value` encoded with bincode's standard configuration, as [FrameEncoder] writes it
+fn encode<T: Encode>(value: &T) -> Vec<u8> {
+    bincode::encode_to_vec(value, bincode::config::standard()).unwrap()
+}
+
+/// a core running the `schedutil` governor at 2 GHz
+fn info() -> PerCpuInformation {
+    PerCpuInformation {
+        governor: "schedutil".into(),
+        megahertz: Some(2000),
+        khz: Some(2_000_000),
+        online: true,
+        frequency: FrequencyReading::Known(2_000_000),
+        ..Default::default()
+    }
+}
+
+fn requests() -> Vec<(Request, &'static [u8])> {
+    vec![
+        (Request::Get(CpuCores::One(4)), &[0, 1, 4]),
+        (
+            Request::Set(
+                CpuCores::Range(0, 3),
+                ScalingType::Preset("performance".into()),
+            ),
+            &[
+                1, 3, 0, 3, 0, 11, 112, 101, 114, 102, 111, 114, 109, 97, 110, 99, 101,
+            ],
+        ),
+        (
+            Request::List(CpuCores::Multiple(vec![0, 2])),
+            &[2, 2, 2, 0, 2],
+        ),
+        (
+            Request::SetWhere {
+                current_governor: "powersave".into(),
+                new: ScalingType::AdjustMax(-100_000),
+            },
+            &[
+                3, 9, 112, 111, 119, 101, 114, 115, 97, 118, 101, 1, 252, 63, 13, 3, 0,
+            ],
+        ),
+        (Request::GetPackages, &[4]),
+        (Request::GetPlatformProfile, &[5]),
+        (
+            Request::SetPlatformProfile("balanced".into()),
+            &[6, 8, 98, 97, 108, 97, 110, 99, 101, 100],
+        ),
+        (Request::DumpCore(1), &[7, 1]),
+        (Request::WatchChanges(CpuCores::All), &[8, 0]),
+        (Request::GetPstateStatus, &[9]),
+        (
+            Request::SetPstateStatus("passive".into()),
+            &[10, 7, 112, 97, 115, 115, 105, 118, 101],
+        ),
+        (Request::Capabilities, &[11]),
+        (Request::CoreCount, &[12]),
+        (Request::SetSmt(false), &[13, 0]),
+        (
+            Request::GetWithOptions(CpuCores::All, GetOptions::default()),
+            &[14, 0, 0, 1, 0, 0, 0],
+        ),
+        (Request::ResetAll, &[15]),
+        (Request::GetAbove(2_000_000), &[16, 252, 128, 132, 30, 0]),
+        (Request::GetPower, &[17]),
+        (
+            Request::SetOnline {
+                cores: CpuCores::One(3),
+                online: false,
+                confirmation: Some(7),
+            },
+            &[18, 1, 3, 0, 1, 7],
+        ),
+        (Request::GetStats(CpuCores::One(0)), &[19, 1, 0]),
+        (Request::ResetStats(CpuCores::All), &[20, 0]),
+        (Request::Keepalive, &[21]),
+        (
+            Request::Subscribe(vec![(CpuCores::All, Duration::from_millis(500))]),
+            &[22, 1, 0, 0, 252, 0, 101, 205, 29],
+        ),
+        (Request::GetGovernor(CpuCores::One(2)), &[23, 1, 2]),
+        (
+            Request::ApplyProfile("quiet".into()),
+            &[24, 5, 113, 117, 105, 101, 116],
+        ),
+        (Request::ListProfiles, &[25]),
+        (Request::SubscribeDelta(CpuCores::All, 200), &[26, 0, 200]),
+        (
+            Request::GetTunable {
+                core: 0,
+                name: "up_threshold".into(),
+            },
+            &[
+                27, 0, 12, 117, 112, 95, 116, 104, 114, 101, 115, 104, 111, 108, 100,
+            ],
+        ),
+        (
+            Request::SetTunable {
+                core: 0,
+                name: "up_threshold".into(),
+                value: "80".into(),
+            },
+            &[
+                28, 0, 12, 117, 112, 95, 116, 104, 114, 101, 115, 104, 111, 108, 100, 2, 56, 48,
+            ],
+        ),
+        (Request::GetConfig, &[29]),
+        (Request::WatchExternal(CpuCores::All), &[30, 0]),
+        (Request::ListTunables(0), &[31, 0]),
+        (Request::GetNmiWatchdog, &[32]),
+        (Request::SetNmiWatchdog(true), &[33, 1]),
+        (Request::Unsubscribe(SubscriptionId(1)), &[34, 1]),
+        (Request::Topology, &[35]),
+    ]
+}
+
+/// the other [ScalingType] variants, which only [Request::Set] carries
+fn scaling_types() -> Vec<(ScalingType, &'static [u8])> {
+    vec![
+        (ScalingType::Pin, &[2]),
+        (ScalingType::Park, &[3]),
+        (
+            ScalingType::WithFallback {
+                primary: Governor::Performance,
+                fallback: Governor::Other("conservative".into()),
+                after: Duration::from_secs(30),
+            },
+            &[
+                4, 0, 6, 12, 99, 111, 110, 115, 101, 114, 118, 97, 116, 105, 118, 101, 30, 0,
+            ],
+        ),
+        (
+            ScalingType::MinMax {
+                min_khz: 800_000,
+                max_khz: 3_600_000,
+            },
+            &[5, 252, 0, 53, 12, 0, 252, 128, 238, 54, 0],
+        ),
+    ]
+}
+
+/// maps hold at most one entry, their iteration order and so their encoding is not fixed
+fn responses() -> Vec<(&'static str, Response<StringError>, &'static [u8])> {
+    vec![
+        (
+            "Error",
+            Response::Error(ServerError::Other(StringError("busy".into()))),
+            &[0, 3, 4, 98, 117, 115, 121],
+        ),
+        (
+            "ScalingGovernors",
+            Response::ScalingGovernors(vec!["performance".into(), "powersave".into()]),
+            &[
+                1, 2, 11, 112, 101, 114, 102, 111, 114, 109, 97, 110, 99, 101, 9, 112, 111, 119,
+                101, 114, 115, 97, 118, 101,
+            ],
+        ),
+        (
+            "Information",
+            Response::Information(Information::Table(HashMap::from([(0, info())]))),
+            &[
+                2, 1, 1, 0, 9, 115, 99, 104, 101, 100, 117, 116, 105, 108, 1, 0, 252, 128, 132, 30,
+                0, 3, 251, 208, 7, 252, 128, 132, 30, 0,
+            ],
+        ),
+        ("Ack", Response::Ack, &[3]),
+        (
+            "Packages",
+            Response::Packages(HashMap::from([(
+                0,
+                PackageInformation {
+                    governors: vec!["schedutil".into()],
+                    average_mhz: Some(2000.0),
+                    energy_uj: None,
+                },
+            )])),
+            &[
+                4, 1, 0, 1, 9, 115, 99, 104, 101, 100, 117, 116, 105, 108, 1, 0, 0, 0, 0, 0, 64,
+                159, 64, 0,
+            ],
+        ),
+        (
+            "PlatformProfile",
+            Response::PlatformProfile("balanced".into()),
+            &[5, 8, 98, 97, 108, 97, 110, 99, 101, 100],
+        ),
+        (
+            "PstateStatus",
+            Response::PstateStatus("active".into()),
+            &[6, 6, 97, 99, 116, 105, 118, 101],
+        ),
+        (
+            "Capabilities",
+            Response::Capabilities(Capabilities {
+                driver: Some("acpi-cpufreq".into()),
+                governors: vec!["schedutil".into()],
+                boost_enabled: Some(true),
+                ..Default::default()
+            }),
+            &[
+                7, 1, 12, 97, 99, 112, 105, 45, 99, 112, 117, 102, 114, 101, 113, 1, 9, 115, 99,
+                104, 101, 100, 117, 116, 105, 108, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0,
+            ],
+        ),
+        ("CoreCount", Response::CoreCount(8), &[8, 8]),
+        (
+            "Dump",
+            Response::Dump(HashMap::from([(
+                "scaling_governor".into(),
+                "schedutil".into(),
+            )])),
+            &[
+                9, 1, 16, 115, 99, 97, 108, 105, 110, 103, 95, 103, 111, 118, 101, 114, 110, 111,
+                114, 9, 115, 99, 104, 101, 100, 117, 116, 105, 108,
+            ],
+        ),
+        (
+            "Deferred",
+            Response::Deferred {
+                retry_after_ms: 600,
+            },
+            &[10, 251, 88, 2],
+        ),
+        (
+            "Power",
+            Response::Power(HashMap::from([(0, 12.5)])),
+            &[11, 1, 0, 0, 0, 0, 0, 0, 0, 41, 64],
+        ),
+        (
+            "ConfirmationRequired",
+            Response::ConfirmationRequired(42),
+            &[12, 42],
+        ),
+        (
+            "Stats",
+            Response::Stats(HashMap::from([(
+                0,
+                CoreStats {
+                    total_trans: 10,
+                    time_in_state: vec![(800_000, 150)],
+                },
+            )])),
+            &[13, 1, 0, 10, 1, 252, 0, 53, 12, 0, 150],
+        ),
+        (
+            "Selection",
+            Response::Selection {
+                id: 1,
+                information: Information::All(info()),
+            },
+            &[
+                14, 1, 0, 9, 115, 99, 104, 101, 100, 117, 116, 105, 108, 1, 0, 252, 128, 132, 30,
+                0, 3, 251, 208, 7, 252, 128, 132, 30, 0,
+            ],
+        ),
+        (
+            "Governors",
+            Response::Governors(HashMap::from([(0, "schedutil".into())])),
+            &[15, 1, 0, 9, 115, 99, 104, 101, 100, 117, 116, 105, 108],
+        ),
+        (
+            "Profiles",
+            Response::Profiles(vec![("quiet".into(), "powersave".into())]),
+            &[
+                16, 1, 5, 113, 117, 105, 101, 116, 9, 112, 111, 119, 101, 114, 115, 97, 118, 101,
+            ],
+        ),
+        ("Tunable", Response::Tunable("80".into()), &[17, 2, 56, 48]),
+        (
+            "Config",
+            Response::Config(DaemonConfig {
+                verify_writes: true,
+                min_change_interval: Some(Duration::from_secs(1)),
+                ..Default::default()
+            }),
+            &[18, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        ),
+        (
+            "Applied",
+            Response::Applied {
+                previous: HashMap::from([(0, "powersave".into())]),
+            },
+            &[19, 1, 0, 9, 112, 111, 119, 101, 114, 115, 97, 118, 101],
+        ),
+        (
+            "ExternalChange",
+            Response::ExternalChange {
+                core: 1,
+                previous: "schedutil".into(),
+                current: "performance".into(),
+            },
+            &[
+                20, 1, 9, 115, 99, 104, 101, 100, 117, 116, 105, 108, 11, 112, 101, 114, 102, 111,
+                114, 109, 97, 110, 99, 101,
+            ],
+        ),
+        (
+            "Tunables",
+            Response::Tunables(vec![("up_threshold".into(), "80".into())]),
+            &[
+                21, 1, 12, 117, 112, 95, 116, 104, 114, 101, 115, 104, 111, 108, 100, 2, 56, 48,
+            ],
+        ),
+        ("NmiWatchdog", Response::NmiWatchdog(false), &[22, 0]),
+        (
+            "Subscribed",
+            Response::Subscribed(SubscriptionId(3)),
+            &[23, 3],
+        ),
+        (
+            "Frame",
+            Response::Frame {
+                id: SubscriptionId(3),
+                timestamp_ms: 1_700_000_000_000,
+                frame: Box::new(Response::Information(Information::Uniform {
+                    cores: CpuCores::Range(0, 7),
+                    info: info(),
+                })),
+            },
+            &[
+                24, 3, 253, 0, 104, 229, 207, 139, 1, 0, 0, 2, 2, 3, 0, 7, 9, 115, 99, 104, 101,
+                100, 117, 116, 105, 108, 1, 0, 252, 128, 132, 30, 0, 3, 251, 208, 7, 252, 128, 132,
+                30, 0,
+            ],
+        ),
+        (
+            "Topology",
+            Response::Topology(Topology {
+                present: vec![0, 1],
+                online: vec![0, 1],
+                packages: HashMap::from([(0, vec![0, 1])]),
+                clusters: vec![vec![0, 1]],
+            }),
+            &[25, 2, 0, 1, 2, 0, 1, 1, 0, 2, 0, 1, 1, 2, 0, 1],
+        ),
+    ]
+}
+
+#[test]
+fn requests_match_their_golden_encoding() {
+    for (index, (request, golden)) in requests().into_iter().enumerate() {
+        let encoded = encode(&request);
+        // every variant is covered, in order
+        assert_eq!(usize::from(encoded[0]), index, "{request:?}");
+        assert_eq!(encoded, golden, "{request:?}");
+    }
+    for (scaling, golden) in scaling_types() {
+        assert_eq!(encode(&scaling), golden, "{scaling:?}");
+    }
+}
+
+#[test]
+fn responses_match_their_golden_encoding() {
+    for (index, (name, response, golden)) in responses().into_iter().enumerate() {
+        let encoded = encode(&response);
+        assert_eq!(usize::from(encoded[0]), index, "{name}");
+        assert_eq!(encoded, golden, "{name}");
+    }
+}
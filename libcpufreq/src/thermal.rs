@@ -0,0 +1,45 @@
+//! advice for staying under a temperature ceiling
+
+/// how far below the ceiling, in millidegrees Celsius, the frequency starts being limited
+pub const THERMAL_MARGIN_MC: u64 = 10_000;
+
+/// a `scaling_max_freq` in kHz that should keep a core under `ceiling_mc` when it is at
+/// `current_mc`, both in millidegrees Celsius as read from `thermal_zone*/temp`. below the ceiling
+/// by more than [THERMAL_MARGIN_MC] the full `cpuinfo_max_khz` is allowed, at or above the
+/// ceiling only `cpuinfo_min_khz`, and in between the limit falls linearly from one to the other.
+///
+/// this is only advice; nothing is applied
+pub fn max_freq_for_thermal(
+    current_mc: u64,
+    ceiling_mc: u64,
+    cpuinfo_min_khz: u64,
+    cpuinfo_max_khz: u64,
+) -> u64 {
+    let floor_mc = ceiling_mc.saturating_sub(THERMAL_MARGIN_MC);
+    if current_mc <= floor_mc {
+        return cpuinfo_max_khz;
+    }
+    if current_mc >= ceiling_mc {
+        return cpuinfo_min_khz;
+    }
+    let range = cpuinfo_max_khz.saturating_sub(cpuinfo_min_khz);
+    let over = (current_mc - floor_mc) as f64 / (ceiling_mc - floor_mc) as f64;
+    cpuinfo_max_khz - (range as f64 * over) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_freq_at_below_and_above_the_ceiling() {
+        let limit = |current_mc| max_freq_for_thermal(current_mc, 90_000, 800_000, 3_000_000);
+        // well below, and at the edge of the margin
+        assert_eq!(limit(60_000), 3_000_000);
+        assert_eq!(limit(80_000), 3_000_000);
+        // part way into the margin
+        assert_eq!(limit(85_000), 1_900_000);
+        assert_eq!(limit(90_000), 800_000);
+        assert_eq!(limit(105_000), 800_000);
+    }
+}
@@ -0,0 +1,23 @@
+//! how a system's cores are laid out
+
+use std::collections::HashMap;
+
+use bincode::{Decode, Encode};
+
+/// the present and online cores and how they are grouped, see
+/// [Request::Topology](crate::Request::Topology)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Topology {
+    /// every present core (the `present` cpu list), sorted. one more than the highest is the
+    /// `total` for [CpuCores::resolve](crate::CpuCores::resolve)
+    pub present: Vec<u8>,
+    /// the online cores, sorted. always a subset of `present`
+    pub online: Vec<u8>,
+    /// the online cores of each physical package (socket), sorted
+    pub packages: HashMap<u8, Vec<u8>>,
+    /// the cores of each cpufreq policy (`related_cpus`), which share a governor and limits, eg
+    /// the P and E core clusters of a hybrid cpu. each is sorted and they are ordered by their
+    /// first core. empty on kernels without the policy layout
+    pub clusters: Vec<Vec<u8>>,
+}
@@ -0,0 +1,101 @@
+//! checks for suspicious but valid configurations
+
+use crate::{Governor, PerCpuInformation};
+
+/// something about a CPU's configuration that is probably not what the user intended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// `scaling_min_freq` equals `scaling_max_freq`, so the governor cannot change the frequency
+    Pinned {
+        /// the frequency the CPU is pinned at, in kHz
+        khz: u64,
+    },
+    /// the governor is `powersave` but the minimum frequency is the hardware maximum, so no power
+    /// is saved
+    PowersaveAtMax,
+    /// the governor is `performance` but the maximum frequency is the hardware minimum, so the
+    /// CPU runs as slow as it can
+    PerformanceAtMin,
+    /// `scaling_min_freq` is above `scaling_max_freq`
+    MinAboveMax,
+}
+
+/// flag oddities in the configuration of one CPU. offline CPUs produce no warnings
+pub fn validate_config(info: &PerCpuInformation) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    if !info.online {
+        return warnings;
+    }
+
+    if let (Some(min), Some(max)) = (info.scaling_min_khz, info.scaling_max_khz) {
+        if min == max {
+            warnings.push(Warning::Pinned { khz: min });
+        } else if min > max {
+            warnings.push(Warning::MinAboveMax);
+        }
+    }
+
+    match Governor::from(info.governor.as_str()) {
+        Governor::Powersave
+            if info.scaling_min_khz.is_some() && info.scaling_min_khz == info.cpuinfo_max_khz =>
+        {
+            warnings.push(Warning::PowersaveAtMax)
+        }
+        Governor::Performance
+            if info.scaling_max_khz.is_some() && info.scaling_max_khz == info.cpuinfo_min_khz =>
+        {
+            warnings.push(Warning::PerformanceAtMin)
+        }
+        _ => {}
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// an online core on `governor` with scaling limits `min`..`max` of hardware limits
+    /// 800000..4000000 kHz
+    fn core(governor: &str, min: u64, max: u64) -> PerCpuInformation {
+        PerCpuInformation {
+            governor: governor.into(),
+            online: true,
+            scaling_min_khz: Some(min),
+            scaling_max_khz: Some(max),
+            cpuinfo_min_khz: Some(800_000),
+            cpuinfo_max_khz: Some(4_000_000),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn flags_suspicious_configs() {
+        assert_eq!(validate_config(&core("schedutil", 800_000, 4_000_000)), []);
+        assert_eq!(
+            validate_config(&core("schedutil", 2_000_000, 2_000_000)),
+            [Warning::Pinned { khz: 2_000_000 }]
+        );
+        assert_eq!(
+            validate_config(&core("schedutil", 3_000_000, 2_000_000)),
+            [Warning::MinAboveMax]
+        );
+        assert_eq!(
+            validate_config(&core("powersave", 4_000_000, 4_000_000)),
+            [Warning::Pinned { khz: 4_000_000 }, Warning::PowersaveAtMax]
+        );
+        assert_eq!(
+            validate_config(&core("performance", 800_000, 800_000)),
+            [Warning::Pinned { khz: 800_000 }, Warning::PerformanceAtMin]
+        );
+    }
+
+    #[test]
+    fn offline_cores_have_no_warnings() {
+        let info = PerCpuInformation {
+            online: false,
+            ..core("schedutil", 3_000_000, 2_000_000)
+        };
+        assert_eq!(validate_config(&info), []);
+    }
+}